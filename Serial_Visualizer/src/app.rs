@@ -0,0 +1,1211 @@
+use eframe::egui;
+use egui::{RichText, FontId, Color32, Vec2, Rect, Sense};
+use serialport::{available_ports, SerialPortInfo, SerialPortType};
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::command_history::CommandHistory;
+use crate::csv_export::CsvExporter;
+use crate::frame_builder::FrameBuilder;
+use crate::frame_decoder::{raw_type_idx, FrameEvent};
+use crate::frame_stats::FrameStats;
+use crate::hex_inspector::{self, HexInspector};
+use crate::imu_panel::{self, ImuHistory};
+use crate::macros::{MacroQueue, MacroSet};
+use crate::packet_handler::{PacketHandler, PacketRegistry};
+use crate::packet_schema::PacketSchema;
+use crate::packets::{decodePacket, ImuSample, Packet, PacketError};
+use crate::reconnect::{retry_interval, DeviceIdentity, ReconnectWatch};
+use crate::replay::{ReplayControl, SessionReplay};
+use crate::serial_reader::{SerialReader, SerialEvent};
+use crate::session::{decodeSession, hostTimestamp, readSession, timestampedBase, toHex, SessionRecorder};
+use crate::settings::{AppSettings, ImuSettings};
+use crate::transport::{openTransport, Transport, TransportAddress};
+
+/// Builds the extra packet handlers for each new connection tab.
+pub type HandlerFactory = fn() -> Vec<Box<dyn PacketHandler>>;
+
+/// Open the GUI. Every connection tab gets the handlers from `handler_factory` on top of the
+/// built-in and schema packet types.
+pub fn runGui(handler_factory: HandlerFactory) -> Result<(), eframe::Error>
+{
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1200.0, 800.0]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "Confirm exit",
+        options,
+        Box::new(move |_cc| Box::new(ConnectionTabs::new(handler_factory))),
+    )
+}
+
+/// Independent connections, each with its own port, decoder, console and visualizations,
+/// shown one at a time as tabs or side by side.
+pub struct ConnectionTabs
+{
+    handler_factory: HandlerFactory,
+    /// Stable id per tab, so widget state follows a tab when others are closed.
+    tabs: Vec<(u64, MainFrame)>,
+    next_tab_id: u64,
+    active: usize,
+    /// Show every tab side by side instead of only the active one.
+    tiled: bool,
+}
+
+impl ConnectionTabs
+{
+    pub fn new(handler_factory: HandlerFactory) -> Self
+    {
+        let mut connection_tabs = Self
+        {
+            handler_factory,
+            tabs: Vec::new(),
+            next_tab_id: 0,
+            active: 0,
+            tiled: false,
+        };
+        connection_tabs.addTab();
+        connection_tabs
+    }
+
+    /// Open a new tab with the saved settings and make it the active one.
+    pub fn addTab(&mut self)
+    {
+        let mut main_frame = MainFrame::new();
+        for handler in (self.handler_factory)()
+        {
+            main_frame.registerPacketHandler(handler);
+        }
+        self.tabs.push((self.next_tab_id, main_frame));
+        self.next_tab_id += 1;
+        self.active = self.tabs.len() - 1;
+    }
+
+    /// Close a tab, disconnecting its port. The last tab is never closed.
+    pub fn closeTab(&mut self, idx: usize)
+    {
+        if self.tabs.len() > 1 && idx < self.tabs.len()
+        {
+            self.tabs.remove(idx);
+            self.active = self.active.min(self.tabs.len() - 1);
+        }
+    }
+}
+
+impl eframe::App for ConnectionTabs
+{
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame)
+    {
+        for (_, main_frame) in &mut self.tabs
+        {
+            main_frame.poll(ctx);
+        }
+        egui::TopBottomPanel::top("connection-tabs").show(ctx, |ui|
+        {
+            ui.horizontal(|ui|{
+                let mut close = None;
+                for (idx, (_, main_frame)) in self.tabs.iter().enumerate()
+                {
+                    if ui.selectable_label(idx == self.active, main_frame.tabTitle()).clicked()
+                    {
+                        self.active = idx;
+                    }
+                    if self.tabs.len() > 1 && ui.small_button("x").clicked()
+                    {
+                        close = Some(idx);
+                    }
+                    ui.separator();
+                }
+                if let Some(idx) = close
+                {
+                    self.closeTab(idx);
+                }
+                if ui.add(egui::Button::new("New Connection")).clicked()
+                {
+                    self.addTab();
+                }
+                ui.checkbox(&mut self.tiled, "Side by side");
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui|
+        {
+            if self.tiled
+            {
+                ui.columns(self.tabs.len(), |columns|
+                {
+                    for ((tab_id, main_frame), ui) in self.tabs.iter_mut().zip(columns)
+                    {
+                        ui.push_id(*tab_id, |ui|
+                        {
+                            egui::ScrollArea::both().show(ui, |ui| main_frame.drawUi(ui, ctx));
+                        });
+                    }
+                });
+            }
+            else
+            {
+                let (tab_id, main_frame) = &mut self.tabs[self.active];
+                ui.push_id(*tab_id, |ui|
+                {
+                    egui::ScrollArea::vertical().show(ui, |ui| main_frame.drawUi(ui, ctx));
+                });
+            }
+        });
+    }
+}
+
+pub struct MainFrame
+{
+    //Put State here
+    connect_button_color: Color32,
+    selected_com: String,
+    settings: AppSettings,
+    serial_reader: Option<SerialReader>,
+    /// Device behind the current or last connection, found again by auto-reconnect.
+    connected_device: Option<DeviceIdentity>,
+    reconnect: Option<ReconnectWatch>,
+    console_log: VecDeque<String>,
+    console_log_iter: usize,
+    input_text: String,
+    command_history: CommandHistory,
+    frame_builder: FrameBuilder,
+    replay_path: String,
+    replay: Option<SessionReplay>,
+    /// IMU ranges in effect when the replay opened, restored when it closes or seeks back.
+    replay_imu_settings: Option<ImuSettings>,
+    csv_export: Option<CsvExporter>,
+    /// Host time of the event being applied, used to stamp exported rows.
+    event_time: f64,
+    packet_schema: PacketSchema,
+    /// Decoders for packet types beyond the built-in IMU and ToF ones.
+    packet_registry: PacketRegistry,
+    frame_stats: FrameStats,
+    hex_inspector: HexInspector,
+    /// Command buttons shown while connected, loaded from `settings.macros`.
+    macros: MacroSet,
+    macro_queue: MacroQueue,
+    //Displayed Data
+    tof_resolution: usize,
+    tof_frame_matrix: Vec<u32>,
+    tof_frame_confidence: Vec<u8>,
+    imu_timestamp: u32,
+    accel_matrix: Vec<f32>,
+    gyro_matrix: Vec<f32>,
+    imu_history: ImuHistory,
+    tof_max_dist: u32,
+}
+
+trait InternalHandlers
+{
+    fn handleRawData(&mut self, raw_frame: Vec<u8>) -> Option<ImuSettings>;
+    fn pushConsoleLine(&mut self, line: String);
+    fn pushImuSample(&mut self);
+    fn applyFrameEvent(&mut self, event: FrameEvent) -> Option<ImuSettings>;
+    fn applyRecordedEvents(&mut self, events: Vec<(f64, FrameEvent)>);
+    fn resetDisplayedData(&mut self);
+    fn openReplay(&mut self, replay: SessionReplay);
+    fn closeReplay(&mut self);
+    fn exportTofFrame(&mut self);
+    fn reloadPacketSchema(&mut self);
+    fn openConnection(&mut self, ctx: &egui::Context) -> bool;
+    fn connectedPortVanished(&self) -> bool;
+    fn connectionLost(&mut self);
+}
+
+impl Default for MainFrame 
+{
+    fn default() -> Self 
+    {
+        Self 
+        {
+            //Put defaults for data in the MainFrame Struct
+            connect_button_color: Color32::RED,
+            selected_com: "".to_string(),
+            settings: AppSettings::default(),
+            serial_reader: None,
+            connected_device: None,
+            reconnect: None,
+            console_log: VecDeque::from(vec!["".to_string(); 30]),
+            console_log_iter: 0,
+            input_text: "".to_string(),
+            command_history: CommandHistory::default(),
+            frame_builder: FrameBuilder::default(),
+            replay_path: "".to_string(),
+            replay: None,
+            replay_imu_settings: None,
+            csv_export: None,
+            event_time: 0.0,
+            packet_schema: PacketSchema::default(),
+            packet_registry: PacketRegistry::default(),
+            frame_stats: FrameStats::default(),
+            hex_inspector: HexInspector::default(),
+            macros: MacroSet::default(),
+            macro_queue: MacroQueue::default(),
+            //Displayed Data
+            tof_resolution: 8,
+            tof_frame_matrix: vec![0;64],
+            tof_frame_confidence: vec![0;64],
+            imu_timestamp: 0,
+            accel_matrix: vec![0.0;3],
+            gyro_matrix: vec![0.0;3],
+            imu_history: ImuHistory::default(),
+            tof_max_dist: 1,
+        }
+    }
+}
+
+impl MainFrame
+{
+    /// Saved settings, the last used port, the configured packet schema, command macros and history.
+    pub fn new() -> Self
+    {
+        let settings = AppSettings::load();
+        let macros = MacroSet::loadOrDefault(&settings.macros);
+        let mut main_frame = MainFrame { selected_com: settings.last_port.clone(), settings, macros, command_history: CommandHistory::load(), ..Default::default() };
+        main_frame.reloadPacketSchema();
+        main_frame
+    }
+
+    /// Port path or replay shown on this connection's tab.
+    pub fn tabTitle(&self) -> String
+    {
+        let name = if self.replay.is_some() { "Replay" } else if self.selected_com.is_empty() { "No Port" } else { &self.selected_com };
+        if self.serial_reader.is_some()
+        {
+            format!("{} (connected)", name)
+        }
+        else
+        {
+            name.to_string()
+        }
+    }
+
+    /// Register a handler, replacing any handler already registered for its packet type.
+    pub fn registerPacketHandler(&mut self, handler: Box<dyn PacketHandler>)
+    {
+        self.packet_registry.register(handler);
+    }
+
+    /// Start reading a transport that is already open, e.g. one end of a pty pair in a test harness.
+    /// Nothing is saved to the settings.
+    pub fn attachTransport(&mut self, transport: Box<dyn Transport>, ctx: &egui::Context)
+    {
+        let repaint_ctx = ctx.clone();
+        self.serial_reader = Some(SerialReader::spawn(transport, move || repaint_ctx.request_repaint()));
+        self.connected_device = None;
+        self.reconnect = None;
+        self.hex_inspector.clear();
+        self.connect_button_color = Color32::GREEN;
+    }
+
+    pub fn isConnected(&self) -> bool
+    {
+        self.serial_reader.is_some()
+    }
+
+    /// Console lines received so far, oldest first.
+    pub fn consoleLines(&self) -> impl Iterator<Item = &String>
+    {
+        self.console_log.iter().take(self.console_log_iter)
+    }
+
+    /// Resolution, zone distances and confidences of the latest ToF frame.
+    pub fn tofFrame(&self) -> (usize, &[u32], &[u8])
+    {
+        (self.tof_resolution, &self.tof_frame_matrix, &self.tof_frame_confidence)
+    }
+
+    /// Timestamp, accel (g) and gyro (dps) of the latest IMU sample.
+    pub fn imuSample(&self) -> (u32, &[f32], &[f32])
+    {
+        (self.imu_timestamp, &self.accel_matrix, &self.gyro_matrix)
+    }
+
+    pub fn frameStats(&self) -> &FrameStats
+    {
+        &self.frame_stats
+    }
+}
+
+/// Convert a recorded session to CSV next to it, running it through the same handlers as live data.
+/// IMU samples are scaled with `imu_settings` until the session carries its own IMU config packet.
+pub fn exportSessionCsv(session_path: &Path, imu_settings: &ImuSettings) -> io::Result<String>
+{
+    let exporter = CsvExporter::create(&session_path.with_extension(""))?;
+    let description = exporter.description();
+    let mut converter = MainFrame { csv_export: Some(exporter), ..Default::default() };
+    converter.settings.imu = imu_settings.clone();
+    converter.applyRecordedEvents(decodeSession(readSession(session_path)?));
+    match converter.csv_export.as_mut()
+    {
+        Some(exporter) => exporter.flush()?,
+        None => return Err(io::Error::other(format!("failed writing {}", description))),
+    }
+    Ok(description)
+}
+
+fn returnUartList() -> Vec<SerialPortInfo>
+{
+    //every port type is listed: USB, PCI, Bluetooth and pseudo-terminals
+    available_ports().unwrap_or_default()
+}
+
+/// Typed paths such as ptys are never enumerated, so a path that exists on disk also counts as present.
+fn portPresent(port_list: &[SerialPortInfo], path: &str) -> bool
+{
+    port_list.iter().any(|p| p.port_name == path) || std::path::Path::new(path).exists()
+}
+
+/// Port name plus its type, and for USB ports the ids and strings that tell identical boards apart.
+fn describePort(port: &SerialPortInfo) -> String
+{
+    match &port.port_type
+    {
+        SerialPortType::UsbPort(info) =>
+        {
+            let mut description = format!("{} [USB {:04x}:{:04x}", port.port_name, info.vid, info.pid);
+            for detail in [&info.manufacturer, &info.product].into_iter().flatten()
+            {
+                description.push(' ');
+                description.push_str(detail);
+            }
+            if let Some(serial_number) = &info.serial_number
+            {
+                description.push_str(&format!(" SN {}", serial_number));
+            }
+            description.push(']');
+            description
+        },
+        SerialPortType::PciPort => format!("{} [PCI]", port.port_name),
+        SerialPortType::BluetoothPort => format!("{} [Bluetooth]", port.port_name),
+        SerialPortType::Unknown => format!("{} [Other]", port.port_name),
+    }
+}
+
+impl InternalHandlers for MainFrame
+{
+    /// Returns the new IMU ranges when the frame was a config packet that changed them. They are
+    /// already applied to this tab; persisting them is up to the caller.
+    fn handleRawData(&mut self, raw_frame: Vec<u8>) -> Option<ImuSettings>
+    {
+        //registered handlers (schema layouts and plugins) run alongside any built-in handler
+        let handled = self.packet_registry.dispatch(&raw_frame);
+        if let Some(Err(e)) = &handled
+        {
+            eprintln!("{}", e);
+        }
+        let decoded = decodePacket(&raw_frame, &self.settings.imu);
+        //a frame is good if either decoder accepted it; the built-in one's verdict wins otherwise
+        match (&decoded, &handled)
+        {
+            (Ok(_), _) | (_, Some(Ok(()))) => self.frame_stats.recordFrame(raw_frame[raw_type_idx]),
+            (Err(PacketError::LengthMismatch { .. }), _) => self.frame_stats.recordLengthMismatch(&raw_frame),
+            (Err(PacketError::UnknownType(_)), Some(Err(_))) => self.frame_stats.recordDecodeError(&raw_frame),
+            (Err(PacketError::UnknownType(_)), None) => self.frame_stats.recordUnknownType(&raw_frame),
+        }
+        //send raw data frames to their proper handler.
+        match decoded
+        {
+            Ok(Packet::ImuTimestamp { timestamp }) =>
+            {
+                //empty timestamp from imu
+                self.imu_timestamp = timestamp;
+            },
+            Ok(Packet::Accel { timestamp, accel }) =>
+            {
+                self.imu_timestamp = timestamp;
+                self.accel_matrix.copy_from_slice(&accel);
+                self.pushImuSample();
+            },
+            Ok(Packet::Gyro { timestamp, gyro }) =>
+            {
+                self.imu_timestamp = timestamp;
+                self.gyro_matrix.copy_from_slice(&gyro);
+                self.pushImuSample();
+            },
+            Ok(Packet::Imu { timestamp, accel, gyro }) =>
+            {
+                self.imu_timestamp = timestamp;
+                self.accel_matrix.copy_from_slice(&accel);
+                self.gyro_matrix.copy_from_slice(&gyro);
+                self.pushImuSample();
+            },
+            Ok(Packet::Tof { resolution, distances, confidences }) =>
+            {
+                self.tof_resolution = resolution;
+                self.tof_max_dist = distances.iter().copied().max().unwrap_or(0).max(1);
+                self.tof_frame_matrix = distances;
+                self.tof_frame_confidence = confidences;
+                self.exportTofFrame();
+            },
+            Ok(Packet::ImuConfig(imu_settings)) =>
+            {
+                if !imu_settings.isValid()
+                {
+                    eprintln!("Ignoring IMU config with unsupported ranges: {} g, {} dps", imu_settings.accel_range_g, imu_settings.gyro_range_dps);
+                }
+                else if imu_settings != self.settings.imu
+                {
+                    self.settings.imu = imu_settings.clone();
+                    return Some(imu_settings);
+                }
+            },
+            //counted in the frame statistics
+            Err(_) => {},
+        }
+        None
+    }
+
+    fn pushConsoleLine(&mut self, line: String)
+    {
+        if self.console_log_iter < self.console_log.len()
+        {
+            self.console_log[self.console_log_iter] = line;
+        }
+        else
+        {
+            self.console_log.push_back(line);
+        }
+        if self.console_log_iter < 2000
+        {
+            self.console_log_iter += 1;
+        }
+        else
+        {
+            self.console_log.pop_front();
+        }
+    }
+
+    fn pushImuSample(&mut self)
+    {
+        let mut sample = ImuSample
+        {
+            timestamp: self.imu_timestamp,
+            accel: [0.0; 3],
+            gyro: [0.0; 3],
+        };
+        sample.accel.copy_from_slice(&self.accel_matrix);
+        sample.gyro.copy_from_slice(&self.gyro_matrix);
+        self.imu_history.push(sample);
+        if let Some(exporter) = self.csv_export.as_mut()
+        {
+            if let Err(e) = exporter.writeImu(self.event_time, &sample)
+            {
+                eprintln!("Failed to write \"{}\", export stopped. Error: {}", exporter.description(), e);
+                self.csv_export = None;
+            }
+        }
+    }
+
+    fn exportTofFrame(&mut self)
+    {
+        if let Some(exporter) = self.csv_export.as_mut()
+        {
+            if let Err(e) = exporter.writeTof(self.event_time, self.tof_resolution, &self.tof_frame_matrix, &self.tof_frame_confidence)
+            {
+                eprintln!("Failed to write \"{}\", export stopped. Error: {}", exporter.description(), e);
+                self.csv_export = None;
+            }
+        }
+    }
+
+    fn reloadPacketSchema(&mut self)
+    {
+        self.packet_schema = PacketSchema::loadOrDefault(&self.settings.packet_schema);
+        self.packet_registry.setSchemaHandlers(self.packet_schema.packets.iter()
+            .map(|layout| Box::new(layout.clone()) as Box<dyn PacketHandler>).collect());
+    }
+
+    fn applyFrameEvent(&mut self, event: FrameEvent) -> Option<ImuSettings>
+    {
+        match event
+        {
+            FrameEvent::Line(line) => self.pushConsoleLine(line),
+            FrameEvent::RawFrame(raw_frame) => return self.handleRawData(raw_frame),
+            FrameEvent::ChecksumFailure(raw_frame) => self.frame_stats.recordChecksumFailure(&raw_frame),
+            FrameEvent::InvalidLine(line) => self.frame_stats.recordInvalidLine(&line),
+        }
+        None
+    }
+
+    /// Replayed and converted events are stamped with the time they were recorded, so a CSV
+    /// exported during replay matches one converted from the file.
+    fn applyRecordedEvents(&mut self, events: Vec<(f64, FrameEvent)>)
+    {
+        for (timestamp, event) in events
+        {
+            self.event_time = timestamp;
+            //recorded config packets last until the replay closes and are never saved
+            self.applyFrameEvent(event);
+        }
+    }
+
+    fn resetDisplayedData(&mut self)
+    {
+        let defaults = MainFrame::default();
+        self.console_log = defaults.console_log;
+        self.console_log_iter = defaults.console_log_iter;
+        self.tof_resolution = defaults.tof_resolution;
+        self.tof_frame_matrix = defaults.tof_frame_matrix;
+        self.tof_frame_confidence = defaults.tof_frame_confidence;
+        self.imu_timestamp = defaults.imu_timestamp;
+        self.accel_matrix = defaults.accel_matrix;
+        self.gyro_matrix = defaults.gyro_matrix;
+        self.imu_history = defaults.imu_history;
+        self.tof_max_dist = defaults.tof_max_dist;
+        self.packet_registry.clearLatest();
+        self.frame_stats = defaults.frame_stats;
+    }
+
+    /// Open `selected_com`, a serial port or network address, with the saved settings and start reading it.
+    fn openConnection(&mut self, ctx: &egui::Context) -> bool
+    {
+        match openTransport(&self.selected_com, &self.settings.connection)
+        {
+            Ok(conn) =>
+            {
+                self.attachTransport(conn, ctx);
+                //only serial devices can be found again after unplugging
+                self.connected_device = TransportAddress::parse(&self.selected_com).isSerial()
+                    .then(|| DeviceIdentity::fromPort(&returnUartList(), &self.selected_com));
+                if self.settings.last_port != self.selected_com
+                {
+                    self.settings.last_port = self.selected_com.clone();
+                    let last_port = self.selected_com.clone();
+                    AppSettings::update(|saved| saved.last_port = last_port);
+                }
+                true
+            },
+            Err(e) =>
+            {
+                eprintln!("Failed to open \"{}\". Error: {}", &self.selected_com, e);
+                false
+            },
+        }
+    }
+
+    /// Config packets in the recording change the IMU ranges while it plays, so the tab's own
+    /// ranges are kept aside until the replay closes.
+    fn openReplay(&mut self, replay: SessionReplay)
+    {
+        self.closeReplay();
+        self.replay_imu_settings = Some(self.settings.imu.clone());
+        self.resetDisplayedData();
+        self.replay = Some(replay);
+    }
+
+    fn closeReplay(&mut self)
+    {
+        self.replay = None;
+        if let Some(imu_settings) = self.replay_imu_settings.take()
+        {
+            self.settings.imu = imu_settings;
+        }
+    }
+
+    /// True when the serial device that was opened is no longer listed. Follows the opened device
+    /// rather than the editable port box, which may have been changed since or never named it, as
+    /// for network and simulator transports.
+    fn connectedPortVanished(&self) -> bool
+    {
+        self.serial_reader.is_some()
+            && self.connected_device.as_ref().is_some_and(|device| !portPresent(&returnUartList(), &device.port_name))
+    }
+
+    /// The port failed or vanished. With auto-reconnect on, start waiting for the device to return.
+    fn connectionLost(&mut self)
+    {
+        self.serial_reader = None;
+        self.connect_button_color = Color32::RED;
+        if !self.settings.connection.auto_reconnect
+        {
+            return;
+        }
+        if let Some(device) = self.connected_device.clone()
+        {
+            self.pushConsoleLine(format!("--- {} disconnected, waiting for it to come back ---", device.port_name));
+            self.reconnect = Some(ReconnectWatch::new(device, hostTimestamp()));
+        }
+    }
+}
+
+impl MainFrame
+{
+    /// Take in everything the port or replay produced since the last frame. Runs for every tab,
+    /// shown or not, so background connections keep decoding.
+    pub fn poll(&mut self, ctx: &egui::Context)
+    {
+        //Drain everything the reader thread decoded since the last repaint
+        let mut reader_failed = false;
+        let mut new_imu_settings = None;
+        while let Some(event) = self.serial_reader.as_ref().and_then(|reader| reader.try_recv())
+        {
+            self.event_time = hostTimestamp();
+            let frame_event = match event
+            {
+                SerialEvent::Bytes(chunk) =>
+                {
+                    self.hex_inspector.push(&chunk);
+                    continue;
+                },
+                SerialEvent::Line(line) => FrameEvent::Line(line),
+                SerialEvent::RawFrame(raw_frame) => FrameEvent::RawFrame(raw_frame),
+                SerialEvent::ChecksumFailure(raw_frame) => FrameEvent::ChecksumFailure(raw_frame),
+                SerialEvent::InvalidLine(line) => FrameEvent::InvalidLine(line),
+                SerialEvent::Error(e) =>
+                {
+                    eprintln!("Connection \"{}\" closed. Error: {}", &self.selected_com, e);
+                    reader_failed = true;
+                    continue;
+                },
+            };
+            new_imu_settings = self.applyFrameEvent(frame_event).or(new_imu_settings);
+        }
+        //ranges announced by the connected firmware are kept for next time; replayed ones are not
+        if let Some(imu_settings) = new_imu_settings
+        {
+            AppSettings::update(|saved| saved.imu = imu_settings);
+        }
+        //a port that vanished from the list is treated like a read error
+        if reader_failed || self.connectedPortVanished()
+        {
+            self.connectionLost();
+        }
+        if !self.settings.connection.auto_reconnect
+        {
+            self.reconnect = None;
+        }
+        //the device may come back under another name, so the port follows it
+        let mut found_port = None;
+        if let Some(watch) = self.reconnect.as_mut()
+        {
+            if watch.attemptDue(Instant::now())
+            {
+                found_port = watch.device.findPort(&returnUartList()).map(|path| (path, watch.lost_at));
+            }
+        }
+        if let Some((path, lost_at)) = found_port
+        {
+            self.selected_com = path;
+            if self.openConnection(ctx)
+            {
+                self.pushConsoleLine(format!("--- reconnected to {} after {:.1} s ---", self.selected_com, hostTimestamp() - lost_at));
+            }
+        }
+        if self.reconnect.is_some()
+        {
+            ctx.request_repaint_after(retry_interval);
+        }
+        //Replayed sessions go through the same handlers as live data
+        if let Some(replay) = self.replay.as_mut()
+        {
+            let events = replay.tick();
+            if !replay.paused
+            {
+                ctx.request_repaint();
+            }
+            self.applyRecordedEvents(events);
+        }
+        if let Some(exporter) = self.csv_export.as_mut()
+        {
+            if let Err(e) = exporter.flush()
+            {
+                eprintln!("Failed to write \"{}\", export stopped. Error: {}", exporter.description(), e);
+                self.csv_export = None;
+            }
+        }
+        self.frame_stats.updateRates(hostTimestamp());
+        //macro commands are written once their delay has passed
+        match self.serial_reader.as_ref()
+        {
+            Some(reader) =>
+            {
+                let now = Instant::now();
+                for command in self.macro_queue.takeDue(now)
+                {
+                    if reader.write(&command)
+                    {
+                        println!("{}", String::from_utf8_lossy(&command));
+                    }
+                }
+                if let Some(next_due) = self.macro_queue.nextDue()
+                {
+                    ctx.request_repaint_after(next_due - now);
+                }
+            },
+            None => self.macro_queue.clear(),
+        }
+        if self.serial_reader.is_some()
+        {
+            //keep rates falling to zero when a port goes quiet
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+    }
+
+    /// Everything for this connection: port, replay and export controls, visualizations and console.
+    pub fn drawUi(&mut self, ui: &mut egui::Ui, ctx: &egui::Context)
+    {
+        if ui.add(egui::Button::new(RichText::new("Connect To Robot").color(Color32::BLACK).font(FontId::proportional(20.0))).fill(self.connect_button_color)).clicked()
+        {
+            if self.serial_reader.is_none()
+            {
+                // Connect To Serial Port
+                self.openConnection(ctx);
+            }
+            else 
+            {
+                // Disconnect From Serial Port
+                self.serial_reader = None;
+                self.reconnect = None;
+            }
+        }
+        //Business logic for Serial First, after running connection logic
+        let port_list = returnUartList();
+        if self.serial_reader.is_none()
+        {
+            self.connect_button_color = Color32::RED;
+            if let Some(watch) = &self.reconnect
+            {
+                let device_name = watch.device.port_name.clone();
+                ui.horizontal(|ui|{
+                    ui.label(format!("Waiting for {} to reconnect", device_name));
+                    if ui.add(egui::Button::new("Stop Waiting")).clicked()
+                    {
+                        self.reconnect = None;
+                    }
+                });
+            }
+            let selected_text = match port_list.iter().find(|p| p.port_name == self.selected_com)
+            {
+                Some(p) => describePort(p),
+                None if self.selected_com.is_empty() => "No Ports".to_string(),
+                None => self.selected_com.to_string(),
+            };
+            ui.horizontal(|ui|{
+                egui::ComboBox::from_id_source("my-combobox")
+                    .selected_text(selected_text)
+                    .width(400.0)
+                    .show_ui(ui, |ui|
+                    {
+                        let sel_com_borrow = &mut self.selected_com;
+                        for p in &port_list
+                        {
+                            ui.selectable_value(sel_com_borrow, p.port_name.clone(), describePort(p));
+                        }
+                    });
+                //any device path can be typed here, e.g. a socat pty or a /dev/serial/by-id link, or a network address
+                ui.add(egui::TextEdit::singleline(&mut self.selected_com).hint_text("device path, tcp://host:port, tcp-listen://addr:port, udp://addr:port or sim://").desired_width(400.0));
+                //no robot needed: the simulator streams IMU and ToF frames with its default options
+                if ui.add(egui::Button::new("Simulator")).clicked()
+                {
+                    self.selected_com = "sim://".to_string();
+                }
+            });
+            //settings only apply when the port is opened, so they are editable while disconnected
+            let previous_connection = self.settings.connection.clone();
+            self.settings.connection.drawEditor(ui);
+            if self.settings.connection != previous_connection
+            {
+                let connection = self.settings.connection.clone();
+                AppSettings::update(|saved| saved.connection = connection);
+            }
+            //recorded sessions can be replayed without hardware
+            ui.horizontal(|ui|{
+                ui.add(egui::TextEdit::singleline(&mut self.replay_path).hint_text("session file").desired_width(400.0));
+                if ui.add(egui::Button::new("Open Session")).clicked()
+                {
+                    match SessionReplay::load(Path::new(&self.replay_path))
+                    {
+                        Ok(replay) => self.openReplay(replay),
+                        Err(e) => eprintln!("Failed to open \"{}\". Error: {}", &self.replay_path, e),
+                    }
+                }
+                if ui.add(egui::Button::new("Export Session CSV")).clicked()
+                {
+                    match exportSessionCsv(Path::new(&self.replay_path), &self.settings.imu)
+                    {
+                        Ok(description) => println!("Exported {}", description),
+                        Err(e) => eprintln!("Failed to export \"{}\". Error: {}", &self.replay_path, e),
+                    }
+                }
+            });
+        }
+        else if let Some(reader) = self.serial_reader.as_ref()
+        {
+            ui.horizontal(|ui|{
+                for command_macro in &self.macros.macros
+                {
+                    if ui.add(egui::Button::new(&command_macro.name)).clicked()
+                    {
+                        self.macro_queue.start(command_macro, Instant::now());
+                    }
+                }
+                //session recording runs on the reader thread so timestamps match when bytes arrived
+                match reader.recordingPath()
+                {
+                    Some(path) =>
+                    {
+                        if ui.add(egui::Button::new(RichText::new("Stop Recording").color(Color32::BLACK)).fill(Color32::RED)).clicked()
+                        {
+                            reader.stopRecording();
+                        }
+                        ui.label(format!("Recording to {}", path));
+                    },
+                    None =>
+                    {
+                        if ui.add(egui::Button::new("Record")).clicked()
+                        {
+                            match SessionRecorder::createTimestamped(Path::new(""))
+                            {
+                                Ok(recorder) => reader.startRecording(recorder),
+                                Err(e) => eprintln!("Failed to create session file. Error: {}", e),
+                            }
+                        }
+                    },
+                }
+                ui.end_row();
+            });
+        }
+        if let Some(replay) = self.replay.as_mut()
+        {
+            match replay.drawControls(ui)
+            {
+                ReplayControl::Nothing => {},
+                ReplayControl::StepFrame =>
+                {
+                    let events = replay.stepFrame();
+                    self.applyRecordedEvents(events);
+                },
+                ReplayControl::Seek(offset) =>
+                {
+                    let events = replay.seek(offset);
+                    self.resetDisplayedData();
+                    //the session is decoded again from its start, with the ranges it started with
+                    if let Some(imu_settings) = self.replay_imu_settings.clone()
+                    {
+                        self.settings.imu = imu_settings;
+                    }
+                    self.applyRecordedEvents(events);
+                },
+                ReplayControl::Close => self.closeReplay(),
+            }
+        }
+        //per-project command buttons, edited here or directly in the macro file
+        egui::CollapsingHeader::new("Macros").id_source("macro-editor").show(ui, |ui|
+        {
+            self.macros.drawEditor(ui);
+            ui.horizontal(|ui|{
+                ui.label(&self.settings.macros);
+                if ui.add(egui::Button::new("Save Macros")).clicked()
+                {
+                    self.macros.save(&self.settings.macros);
+                }
+                if ui.add(egui::Button::new("Reload Macros")).clicked()
+                {
+                    self.macros = MacroSet::loadOrDefault(&self.settings.macros);
+                }
+            });
+        });
+        //live CSV export of whatever is being decoded, from a port or a replay
+        ui.horizontal(|ui|{
+            match self.csv_export.as_mut()
+            {
+                Some(exporter) =>
+                {
+                    let description = exporter.description();
+                    if ui.add(egui::Button::new(RichText::new("Stop CSV Export").color(Color32::BLACK)).fill(Color32::RED)).clicked()
+                    {
+                        if let Err(e) = exporter.flush()
+                        {
+                            eprintln!("Failed to write \"{}\". Error: {}", description, e);
+                        }
+                        self.csv_export = None;
+                    }
+                    ui.label(format!("Exporting to {}", description));
+                },
+                None =>
+                {
+                    if ui.add(egui::Button::new("Export CSV")).clicked()
+                    {
+                        let base = timestampedBase(Path::new(""), "export", &["_tof.csv", "_imu.csv"]);
+                        match CsvExporter::create(&base)
+                        {
+                            Ok(exporter) => self.csv_export = Some(exporter),
+                            Err(e) => eprintln!("Failed to create CSV files. Error: {}", e),
+                        }
+                    }
+                },
+            }
+        });
+        //Visualize ToF Data and IMU data
+        ui.horizontal(|ui|{
+            //ToF data First
+            let (response, painter) = ui.allocate_painter(Vec2::new(384.0, 256.0), Sense::hover());
+            let rect = response.rect;
+            let top_left_tof = rect.left_top();
+            //the grid is always 256 px square, so 4x4 zones are drawn twice as large as 8x8 zones
+            let res = self.tof_resolution;
+            let zone_size = 256.0 / res as f32;
+            for row in 0..res
+            {
+                for column in 0..res
+                {
+                    let zone_idx = row * res + (res - 1 - column);
+                    let new_top_left = top_left_tof + Vec2::new(column as f32 * zone_size, row as f32 * zone_size);
+                    let new_bottom_left = new_top_left + Vec2::new(zone_size, zone_size);
+                    let new_tof_rect = Rect::from_two_pos(new_top_left, new_bottom_left);
+                    let dist_hue = (self.tof_frame_matrix[zone_idx] as f32 / self.tof_max_dist as f32) * 0.875;
+                    let dist_color = eframe::epaint::Hsva::new(dist_hue, 1.0, 1.0, 1.0);
+                    painter.rect_filled(new_tof_rect, egui::Rounding::ZERO, Color32::from(dist_color));
+                    painter.text(
+                        new_top_left + Vec2::new(zone_size / 2.0, zone_size / 2.0),
+                        egui::Align2::CENTER_CENTER,
+                        format!("{:}", self.tof_frame_confidence[zone_idx]),
+                        FontId::proportional(12.0),
+                        Color32::BLACK,
+                    );
+                }
+            }
+            let top_left_guide = response.rect.left_top() + Vec2::new(288.0, 0.0);
+            for row_2 in 0..16
+            {
+                let new_top_left = top_left_guide + Vec2::new(0.0, (row_2 as u16 * 16) as f32);
+                let new_bottom_left = new_top_left + Vec2::new(32.0, 16.0);
+                let new_tof_rect = Rect::from_two_pos(new_top_left, new_bottom_left);
+                painter.rect_filled(new_tof_rect, egui::Rounding::ZERO, Color32::from(eframe::epaint::Hsva::new((row_2 as f32) / 16.0, 1.0, 1.0, 1.0)));
+            }
+            painter.text(
+                rect.right_top() + Vec2::new(-60.0, 8.0),
+                egui::Align2::LEFT_CENTER,
+                format!("{:.0} mm", 0),
+                FontId::proportional(12.0),
+                Color32::BLACK,
+            );
+            painter.text(
+                rect.right_bottom() + Vec2::new(-60.0, -8.0),
+                egui::Align2::LEFT_CENTER,
+                format!("{} mm", self.tof_max_dist),
+                FontId::proportional(12.0),
+                Color32::BLACK,
+            );
+            //IMU readouts and plots next to the ToF grid
+            ui.vertical(|ui|{
+                //ranges must match the firmware, either picked here or sent in an IMU config packet
+                let previous_imu = self.settings.imu.clone();
+                self.settings.imu.drawEditor(ui);
+                if self.settings.imu != previous_imu
+                {
+                    let imu_settings = self.settings.imu.clone();
+                    //picked by hand, so it outlasts a replay
+                    if let Some(replay_imu_settings) = self.replay_imu_settings.as_mut()
+                    {
+                        *replay_imu_settings = imu_settings.clone();
+                    }
+                    AppSettings::update(|saved| saved.imu = imu_settings);
+                }
+                imu_panel::drawImuPanel(ui, self.imu_timestamp, &self.accel_matrix, &self.gyro_matrix, &self.imu_history);
+            });
+        });
+        //Packets decoded by registered handlers
+        ui.horizontal(|ui|{
+            ui.label(format!("Packet schema: {} ({} types)", self.settings.packet_schema, self.packet_schema.packets.len()));
+            if ui.add(egui::Button::new("Reload Schema")).clicked()
+            {
+                self.reloadPacketSchema();
+            }
+        });
+        self.packet_registry.drawPanels(ui);
+        self.frame_stats.drawPanel(ui);
+        //raw bytes of the live port with frame headers and checksums marked
+        egui::CollapsingHeader::new("Hex inspector").id_source("hex-inspector").show(ui, |ui|
+        {
+            self.hex_inspector.drawPanel(ui);
+            if let Some(frame) = self.hex_inspector.selectedFrame()
+            {
+                hex_inspector::drawFrameDetails(ui, frame, &self.settings.imu, &mut self.packet_registry);
+            }
+        });
+        //Console Logs at bottom
+        ui.heading("Output Log:");
+        let default_spacing = ui.spacing().item_spacing.y;
+        ui.spacing_mut().item_spacing.y = 0.0;
+        egui::ScrollArea::vertical().stick_to_bottom(true).auto_shrink([false; 2]).max_height(400.0).max_width(1130.0).show(ui, |ui|
+        {
+            for row in 0..self.console_log.len() 
+            {
+                ui.add(egui::Label::new(RichText::new(format!("{:160}",
+                    self.console_log[row]))
+                    .color(Color32::GREEN)
+                    .background_color(Color32::BLACK)
+                    .font(FontId::monospace(12.0))).truncate(true));
+            }
+        });
+        ui.spacing_mut().item_spacing.y = default_spacing;
+        ui.add_space(8.0);
+        //Text box to send text with
+        ui.horizontal(|ui| {
+            //macro commands autocomplete too
+            let mut known_commands = self.settings.known_commands.clone();
+            known_commands.extend(self.macros.macros.iter().flat_map(|command_macro| command_macro.commands.iter().cloned()));
+            let send = self.command_history.drawSendBox(ui, &mut self.input_text, &known_commands);
+            let previous_line_ending = self.settings.send_line_ending;
+            self.settings.send_line_ending.drawSelector(ui, "send-line-ending");
+            if self.settings.send_line_ending != previous_line_ending
+            {
+                let line_ending = self.settings.send_line_ending;
+                AppSettings::update(|saved| saved.send_line_ending = line_ending);
+            }
+            if send
+            {
+                if let Some(reader) = self.serial_reader.as_ref()
+                {
+                    let command = format!("{}{}", self.input_text, self.settings.send_line_ending.text());
+                    if reader.write(command.as_bytes())
+                    {
+                        println!("{}", &command);
+                        self.command_history.push(&self.input_text);
+                        self.input_text = "".to_string();
+                    }
+                }
+            }
+        });
+        //binary configuration frames and arbitrary bytes
+        egui::CollapsingHeader::new("Send Frames").id_source("frame-builder").show(ui, |ui|
+        {
+            if let Some(bytes) = self.frame_builder.drawPanel(ui, &self.packet_schema)
+            {
+                if let Some(reader) = self.serial_reader.as_ref()
+                {
+                    if reader.write(&bytes)
+                    {
+                        println!("sent {}", toHex(&bytes));
+                    }
+                }
+            }
+        });
+        ui.end_row();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::frame_decoder::buildRawFrame;
+    use crate::packets::{imu_config_type, imu_type, tof_type};
+    use crate::session::SessionRecord;
+    use crate::settings::settings_file;
+
+    /// IMU frame with every raw axis at 0x4000, half of full scale.
+    fn halfScaleImuFrame() -> Vec<u8>
+    {
+        let mut payload = vec![0x01, 0x00, 0x00];
+        payload.extend([0x00, 0x40].repeat(6));
+        buildRawFrame(imu_type, &payload).unwrap()
+    }
+
+    #[test]
+    fn session_export_applies_config_packets_without_saving_settings()
+    {
+        let session_path = std::env::temp_dir().join(format!("sv_export_config_{}.svlog", std::process::id()));
+        let mut recorder = SessionRecorder::create(&session_path).unwrap();
+        recorder.write(10.0, &SessionRecord::Frame(halfScaleImuFrame())).unwrap();
+        //16 g, 250 dps
+        recorder.write(11.0, &SessionRecord::Frame(buildRawFrame(imu_config_type, &[16, 250, 0]).unwrap())).unwrap();
+        recorder.write(12.0, &SessionRecord::Frame(halfScaleImuFrame())).unwrap();
+        recorder.flush().unwrap();
+        let saved_settings = std::fs::read_to_string(settings_file).ok();
+        let imu_settings = ImuSettings { accel_range_g: 8, gyro_range_dps: 500 };
+        exportSessionCsv(&session_path, &imu_settings).unwrap();
+        assert_eq!(std::fs::read_to_string(settings_file).ok(), saved_settings);
+        let imu_csv_path = std::env::temp_dir().join(format!("sv_export_config_{}_imu.csv", std::process::id()));
+        let imu_csv = std::fs::read_to_string(&imu_csv_path).unwrap();
+        let rows: Vec<&str> = imu_csv.lines().skip(1).collect();
+        assert_eq!(rows, vec!["10.000000,1,4,4,4,250,250,250", "12.000000,1,8,8,8,125,125,125"]);
+        for path in [session_path.clone(), imu_csv_path, session_path.with_extension("").with_file_name(format!("sv_export_config_{}_tof.csv", std::process::id()))]
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn replaying_a_session_exports_the_same_csv_as_converting_it()
+    {
+        let dir = std::env::temp_dir();
+        let session_path = dir.join(format!("sv_round_trip_{}.svlog", std::process::id()));
+        let mut recorder = SessionRecorder::create(&session_path).unwrap();
+        recorder.write(1000.25, &SessionRecord::Line("tof ready".to_string())).unwrap();
+        recorder.write(1000.5, &SessionRecord::Frame(buildRawFrame(tof_type, &[0xB0, 0x04, 80].repeat(16)).unwrap())).unwrap();
+        recorder.write(1000.75, &SessionRecord::Frame(halfScaleImuFrame())).unwrap();
+        recorder.flush().unwrap();
+        exportSessionCsv(&session_path, &ImuSettings::default()).unwrap();
+        let replay_base = dir.join(format!("sv_round_trip_{}_replayed", std::process::id()));
+        let mut main_frame = MainFrame { csv_export: Some(CsvExporter::create(&replay_base).unwrap()), ..Default::default() };
+        let mut replay = SessionReplay::load(&session_path).unwrap();
+        let events = replay.seek(replay.duration());
+        main_frame.applyRecordedEvents(events);
+        main_frame.csv_export.as_mut().unwrap().flush().unwrap();
+        //rows carry the recorded host time, not the time of the export
+        for (suffix, row_start) in [("_tof.csv", "1000.500000,4x4,1200,"), ("_imu.csv", "1000.750000,1,2,2,2,1000,")]
+        {
+            let converted_path = dir.join(format!("sv_round_trip_{}{}", std::process::id(), suffix));
+            let replayed_path = dir.join(format!("sv_round_trip_{}_replayed{}", std::process::id(), suffix));
+            let converted = std::fs::read_to_string(&converted_path).unwrap();
+            assert_eq!(converted.lines().count(), 2);
+            assert!(converted.lines().nth(1).unwrap().starts_with(row_start));
+            assert_eq!(converted, std::fs::read_to_string(&replayed_path).unwrap());
+            let _ = std::fs::remove_file(converted_path);
+            let _ = std::fs::remove_file(replayed_path);
+        }
+        let _ = std::fs::remove_file(session_path);
+    }
+
+    #[test]
+    fn wrong_length_frames_with_a_schema_layout_are_not_counted_as_decoded()
+    {
+        let schema: PacketSchema = toml::from_str(r#"
+            [[packet]]
+            type_id = 7
+            name = "battery"
+            length = 2
+            field = [{ name = "millivolts", width = 2 }]
+
+            [[packet]]
+            type_id = 3
+            name = "imu raw"
+            length = 15
+            field = [{ name = "timestamp", width = 3 }]
+        "#).unwrap();
+        let mut main_frame = MainFrame::default();
+        main_frame.packet_registry.setSchemaHandlers(schema.packets.into_iter().map(|layout| Box::new(layout) as Box<dyn PacketHandler>).collect());
+        main_frame.handleRawData(buildRawFrame(7, &[0x2E, 0xE0]).unwrap());
+        main_frame.handleRawData(buildRawFrame(7, &[0x2E]).unwrap());
+        main_frame.handleRawData(buildRawFrame(imu_type, &[0; 10]).unwrap());
+        let battery = main_frame.frameStats().typeStats(7).unwrap();
+        assert_eq!((battery.frames, battery.decode_errors), (1, 1));
+        let imu = main_frame.frameStats().typeStats(imu_type).unwrap();
+        assert_eq!((imu.frames, imu.length_mismatches), (0, 1));
+    }
+
+    #[test]
+    fn closing_a_replay_restores_the_tab_imu_ranges()
+    {
+        let session_path = std::env::temp_dir().join(format!("sv_replay_ranges_{}.svlog", std::process::id()));
+        let mut recorder = SessionRecorder::create(&session_path).unwrap();
+        recorder.write(1.0, &SessionRecord::Frame(buildRawFrame(imu_config_type, &[16, 250, 0]).unwrap())).unwrap();
+        recorder.flush().unwrap();
+        let tab_ranges = ImuSettings { accel_range_g: 8, gyro_range_dps: 500 };
+        let mut main_frame = MainFrame::default();
+        main_frame.settings.imu = tab_ranges.clone();
+        main_frame.openReplay(SessionReplay::load(&session_path).unwrap());
+        let events = main_frame.replay.as_mut().unwrap().seek(1.0);
+        main_frame.applyRecordedEvents(events);
+        assert_eq!(main_frame.settings.imu, ImuSettings { accel_range_g: 16, gyro_range_dps: 250 });
+        main_frame.closeReplay();
+        assert_eq!(main_frame.settings.imu, tab_ranges);
+        let _ = std::fs::remove_file(session_path);
+    }
+}
@@ -0,0 +1,138 @@
+#![allow(non_snake_case)]
+
+//! Opens a port with the GUI's saved settings and streams what it decodes to stdout as JSON lines.
+//!
+//! Usage: serial_headless [PORT]. The port defaults to the last one the GUI connected to, and may
+//! also be a network address such as `tcp://host:port`, `tcp-listen://addr:port` or `udp://addr:port`,
+//! or `sim://` for the built-in firmware simulator.
+//!
+//! Every object has a `time` (host unix seconds) and a `kind`:
+//! - `{"kind":"line","text":...}` for text lines,
+//! - `{"kind":"packet","packet":"tof"|"imu"|...,...}` for built-in raw frames,
+//! - `{"kind":"packet","packet":"schema","type_id":...,"name":...,"fields":[...]}` for schema packet types.
+//!
+//! Errors and frames that cannot be decoded are reported on stderr.
+
+use serde_json::{json, Value};
+use std::io::{self, ErrorKind, Write};
+use std::process::ExitCode;
+
+use Serial_Visualizer::packet_schema::PacketSchema;
+use Serial_Visualizer::packets::{decodePacket, Packet, PacketError};
+use Serial_Visualizer::serial_reader::{SerialEvent, SerialReader};
+use Serial_Visualizer::session::hostTimestamp;
+use Serial_Visualizer::settings::{AppSettings, ImuSettings};
+use Serial_Visualizer::transport::openTransport;
+
+/// `fields` plus `time` and `kind`, as one line of JSON.
+fn jsonLine(kind: &str, fields: Value) -> String
+{
+    let mut line = json!({ "time": hostTimestamp(), "kind": kind });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields)
+    {
+        line.extend(fields);
+    }
+    line.to_string()
+}
+
+/// Decode a raw frame. IMU config packets update `imu_settings` for the frames after them.
+fn rawFrameJson(raw_frame: &[u8], imu_settings: &mut ImuSettings, schema: &PacketSchema) -> Result<Value, String>
+{
+    match decodePacket(raw_frame, imu_settings)
+    {
+        Ok(packet) =>
+        {
+            //like the GUI, but the saved settings are never touched
+            if let Packet::ImuConfig(config) = &packet
+            {
+                if config.isValid()
+                {
+                    *imu_settings = config.clone();
+                }
+                else
+                {
+                    eprintln!("Ignoring IMU config with unsupported ranges: {} g, {} dps", config.accel_range_g, config.gyro_range_dps);
+                }
+            }
+            serde_json::to_value(packet).map_err(|e| e.to_string())
+        },
+        Err(PacketError::UnknownType(packet_type)) =>
+        {
+            let layout = schema.layout(packet_type).ok_or(format!("invalid data type {}", packet_type))?;
+            let mut value = serde_json::to_value(layout.decode(raw_frame)?).map_err(|e| e.to_string())?;
+            if let Some(object) = value.as_object_mut()
+            {
+                object.insert("packet".to_string(), json!("schema"));
+            }
+            Ok(value)
+        },
+        Err(PacketError::LengthMismatch { packet_type, length }) => Err(format!("type {} frame with unexpected length {}", packet_type, length)),
+    }
+}
+
+fn main() -> ExitCode
+{
+    let settings = AppSettings::load();
+    let port_path = std::env::args().nth(1).unwrap_or(settings.last_port.clone());
+    if port_path.is_empty()
+    {
+        eprintln!("usage: serial_headless [PORT]");
+        return ExitCode::FAILURE;
+    }
+    let schema = PacketSchema::loadOrDefault(&settings.packet_schema);
+    let mut imu_settings = settings.imu.clone();
+    let port = match openTransport(&port_path, &settings.connection)
+    {
+        Ok(port) => port,
+        Err(e) =>
+        {
+            eprintln!("Failed to open \"{}\". Error: {}", port_path, e);
+            return ExitCode::FAILURE;
+        },
+    };
+    let reader = SerialReader::spawn(port, || {});
+    let mut stdout = io::stdout().lock();
+    while let Some(event) = reader.recv()
+    {
+        let output = match event
+        {
+            SerialEvent::Bytes(_) => None,
+            SerialEvent::Line(line) => Some(jsonLine("line", json!({ "text": line }))),
+            SerialEvent::RawFrame(raw_frame) => match rawFrameJson(&raw_frame, &mut imu_settings, &schema)
+            {
+                Ok(packet) => Some(jsonLine("packet", packet)),
+                Err(e) =>
+                {
+                    eprintln!("{}", e);
+                    None
+                },
+            },
+            SerialEvent::ChecksumFailure(raw_frame) =>
+            {
+                eprintln!("checksum failure, dropped {} byte frame", raw_frame.len());
+                None
+            },
+            SerialEvent::InvalidLine(_) =>
+            {
+                eprintln!("not a valid utf-8 string, dropping.");
+                None
+            },
+            SerialEvent::Error(e) =>
+            {
+                eprintln!("Connection \"{}\" closed. Error: {}", port_path, e);
+                return ExitCode::FAILURE;
+            },
+        };
+        if let Some(Err(e)) = output.map(|output| writeln!(stdout, "{}", output))
+        {
+            //whoever reads the output (head, jq, ...) has finished with it
+            if e.kind() == ErrorKind::BrokenPipe
+            {
+                return ExitCode::SUCCESS;
+            }
+            eprintln!("Failed to write to stdout. Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
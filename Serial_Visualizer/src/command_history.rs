@@ -0,0 +1,250 @@
+#[cfg(feature = "gui")]
+use eframe::egui::{self, Key, Modifiers, text::{CCursor, CCursorRange}};
+use std::io;
+use std::path::Path;
+
+/// Sent commands are kept in this file in the working directory, one per line, oldest first.
+pub const history_file: &str = "Serial_Visualizer_history.txt";
+
+const max_history: usize = 1000;
+const max_completions: usize = 8;
+
+/// Commands sent from the send box, browsable with the arrow keys and used for autocomplete.
+#[derive(Default)]
+pub struct CommandHistory
+{
+    entries: Vec<String>,
+    /// Entry shown while browsing with up/down, None when editing a new command.
+    browse_idx: Option<usize>,
+    /// What was typed before browsing started, restored when browsing past the newest entry.
+    draft: String,
+}
+
+impl CommandHistory
+{
+    /// Load the saved history. A missing file is an empty history.
+    pub fn load() -> Self
+    {
+        Self { entries: readEntries(Path::new(history_file)).unwrap_or_default(), ..Default::default() }
+    }
+
+    /// Add a sent command, skipping repeats of the last one, and save the history.
+    pub fn push(&mut self, command: &str)
+    {
+        self.pushTo(Path::new(history_file), command);
+    }
+
+    fn pushTo(&mut self, path: &Path, command: &str)
+    {
+        self.browse_idx = None;
+        if command.is_empty()
+        {
+            return;
+        }
+        //other tabs save to the same file, so build on what is saved rather than this tab's copy
+        let mut entries = readEntries(path).unwrap_or_else(|_| std::mem::take(&mut self.entries));
+        if entries.last().is_some_and(|last| last == command)
+        {
+            self.entries = entries;
+            return;
+        }
+        entries.push(command.to_string());
+        if entries.len() > max_history
+        {
+            let excess = entries.len() - max_history;
+            entries.drain(..excess);
+        }
+        let mut contents = entries.join("\n");
+        contents.push('\n');
+        if let Err(e) = std::fs::write(path, contents)
+        {
+            eprintln!("Failed to write \"{}\". Error: {}", path.display(), e);
+        }
+        self.entries = entries;
+    }
+
+    /// Step back one entry, remembering `current` as the draft when browsing starts.
+    pub fn older(&mut self, current: &str) -> Option<String>
+    {
+        let idx = match self.browse_idx
+        {
+            Some(idx) => idx.checked_sub(1)?,
+            None =>
+            {
+                self.draft = current.to_string();
+                self.entries.len().checked_sub(1)?
+            },
+        };
+        self.browse_idx = Some(idx);
+        Some(self.entries[idx].clone())
+    }
+
+    /// Step forward one entry, back to the draft after the newest one.
+    pub fn newer(&mut self) -> Option<String>
+    {
+        let idx = self.browse_idx? + 1;
+        if idx < self.entries.len()
+        {
+            self.browse_idx = Some(idx);
+            Some(self.entries[idx].clone())
+        }
+        else
+        {
+            self.browse_idx = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    /// Commands starting with `prefix`: history newest first, then `known_commands`.
+    pub fn completions(&self, prefix: &str, known_commands: &[String]) -> Vec<String>
+    {
+        let mut completions: Vec<String> = Vec::new();
+        if prefix.is_empty()
+        {
+            return completions;
+        }
+        for command in self.entries.iter().rev().chain(known_commands)
+        {
+            if command.starts_with(prefix) && command != prefix && !completions.contains(command)
+            {
+                completions.push(command.clone());
+                if completions.len() == max_completions
+                {
+                    break;
+                }
+            }
+        }
+        completions
+    }
+
+    /// Single line command box. Up/down browse history, Tab takes the first completion and
+    /// Enter sends. Returns true when the command in `input_text` should be sent.
+    #[cfg(feature = "gui")]
+    pub fn drawSendBox(&mut self, ui: &mut egui::Ui, input_text: &mut String, known_commands: &[String]) -> bool
+    {
+        let id = ui.make_persistent_id("send-box");
+        let completions = self.completions(input_text, known_commands);
+        //keys are taken before the text box sees them so they don't also move the cursor
+        let mut replacement = None;
+        if ui.memory(|memory| memory.has_focus(id))
+        {
+            if ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::ArrowUp))
+            {
+                replacement = self.older(input_text);
+            }
+            else if ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::ArrowDown))
+            {
+                replacement = self.newer();
+            }
+            else if !completions.is_empty() && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Tab))
+            {
+                replacement = Some(completions[0].clone());
+            }
+        }
+        if let Some(text) = replacement
+        {
+            *input_text = text;
+            moveCursorToEnd(ui.ctx(), id, input_text);
+        }
+        let response = ui.add(egui::TextEdit::singleline(input_text).id(id).hint_text("send command").desired_width(400.0));
+        if response.changed()
+        {
+            self.browse_idx = None;
+        }
+        let mut send = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+        if send
+        {
+            response.request_focus();
+        }
+        let popup_id = id.with("completions");
+        if response.has_focus() && !completions.is_empty()
+        {
+            ui.memory_mut(|memory| memory.open_popup(popup_id));
+        }
+        else if completions.is_empty() && ui.memory(|memory| memory.is_popup_open(popup_id))
+        {
+            ui.memory_mut(|memory| memory.close_popup());
+        }
+        egui::popup_below_widget(ui, popup_id, &response, |ui|
+        {
+            for completion in &completions
+            {
+                if ui.selectable_label(false, completion).clicked()
+                {
+                    *input_text = completion.clone();
+                    moveCursorToEnd(ui.ctx(), id, input_text);
+                    response.request_focus();
+                }
+            }
+        });
+        if ui.add(egui::Button::new("Send")).clicked()
+        {
+            send = true;
+        }
+        send
+    }
+}
+
+fn readEntries(path: &Path) -> io::Result<Vec<String>>
+{
+    let contents = std::fs::read_to_string(path)?;
+    Ok(contents.lines().filter(|line| !line.is_empty()).map(|line| line.to_string()).collect())
+}
+
+#[cfg(feature = "gui")]
+fn moveCursorToEnd(ctx: &egui::Context, id: egui::Id, text: &str)
+{
+    if let Some(mut state) = egui::TextEdit::load_state(ctx, id)
+    {
+        state.cursor.set_char_range(Some(CCursorRange::one(CCursor::new(text.chars().count()))));
+        state.store(ctx, id);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn history(entries: &[&str]) -> CommandHistory
+    {
+        CommandHistory { entries: entries.iter().map(|entry| entry.to_string()).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn browses_back_and_returns_to_the_draft()
+    {
+        let mut history = history(&["first", "second"]);
+        assert_eq!(history.older("typing").as_deref(), Some("second"));
+        assert_eq!(history.older("second").as_deref(), Some("first"));
+        assert_eq!(history.older("first"), None);
+        assert_eq!(history.newer().as_deref(), Some("second"));
+        assert_eq!(history.newer().as_deref(), Some("typing"));
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn pushes_from_several_tabs_share_the_file()
+    {
+        let path = std::env::temp_dir().join(format!("sv_history_tabs_{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut first_tab = CommandHistory::default();
+        let mut second_tab = CommandHistory::default();
+        first_tab.pushTo(&path, "imu start");
+        second_tab.pushTo(&path, "tof start_measurements");
+        first_tab.pushTo(&path, "tof stop");
+        second_tab.pushTo(&path, "tof stop");
+        assert_eq!(readEntries(&path).unwrap(), vec!["imu start", "tof start_measurements", "tof stop"]);
+        assert_eq!(second_tab.entries, vec!["imu start", "tof start_measurements", "tof stop"]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn completes_from_history_then_known_commands()
+    {
+        let history = history(&["tof stop", "imu start", "tof start_measurements"]);
+        let known = vec!["tof set_resolution 8".to_string(), "tof stop".to_string()];
+        assert_eq!(history.completions("tof", &known), vec!["tof start_measurements", "tof stop", "tof set_resolution 8"]);
+        assert!(history.completions("", &known).is_empty());
+    }
+}
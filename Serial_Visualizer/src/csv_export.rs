@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::packets::ImuSample;
+use crate::session::withSuffix;
+
+//ToF rows always have 64 distance and 64 confidence columns; 4x4 frames leave the tail empty.
+const tof_csv_zones: usize = 64;
+
+/// Writes decoded ToF frames and IMU samples to `<base>_tof.csv` and `<base>_imu.csv`.
+pub struct CsvExporter
+{
+    base: PathBuf,
+    tof_writer: BufWriter<File>,
+    imu_writer: BufWriter<File>,
+}
+
+impl CsvExporter
+{
+    pub fn create(base: &Path) -> io::Result<Self>
+    {
+        let mut tof_writer = BufWriter::new(File::create(withSuffix(base, "_tof.csv"))?);
+        let mut imu_writer = BufWriter::new(File::create(withSuffix(base, "_imu.csv"))?);
+        let mut tof_header = "host_time,resolution".to_string();
+        for zone in 0..tof_csv_zones
+        {
+            tof_header.push_str(&format!(",dist_{}", zone));
+        }
+        for zone in 0..tof_csv_zones
+        {
+            tof_header.push_str(&format!(",conf_{}", zone));
+        }
+        writeln!(tof_writer, "{}", tof_header)?;
+        writeln!(imu_writer, "host_time,imu_timestamp,accel_x,accel_y,accel_z,gyro_x,gyro_y,gyro_z")?;
+        Ok(Self
+        {
+            base: base.to_path_buf(),
+            tof_writer,
+            imu_writer,
+        })
+    }
+
+    /// Display name covering both files, e.g. "export_1700000000_{tof,imu}.csv".
+    pub fn description(&self) -> String
+    {
+        format!("{}_{{tof,imu}}.csv", self.base.display())
+    }
+
+    pub fn writeTof(&mut self, host_time: f64, resolution: usize, distances: &[u32], confidences: &[u8]) -> io::Result<()>
+    {
+        let mut row = format!("{:.6},{}x{}", host_time, resolution, resolution);
+        for zone in 0..tof_csv_zones
+        {
+            row.push(',');
+            if let Some(distance) = distances.get(zone)
+            {
+                row.push_str(&distance.to_string());
+            }
+        }
+        for zone in 0..tof_csv_zones
+        {
+            row.push(',');
+            if let Some(confidence) = confidences.get(zone)
+            {
+                row.push_str(&confidence.to_string());
+            }
+        }
+        writeln!(self.tof_writer, "{}", row)
+    }
+
+    pub fn writeImu(&mut self, host_time: f64, sample: &ImuSample) -> io::Result<()>
+    {
+        writeln!(self.imu_writer, "{:.6},{},{},{},{},{},{},{}", host_time, sample.timestamp,
+            sample.accel[0], sample.accel[1], sample.accel[2],
+            sample.gyro[0], sample.gyro[1], sample.gyro[2])
+    }
+
+    pub fn flush(&mut self) -> io::Result<()>
+    {
+        self.tof_writer.flush()?;
+        self.imu_writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn pads_4x4_frames_to_64_zones()
+    {
+        let base = std::env::temp_dir().join(format!("sv_csv_padding_{}", std::process::id()));
+        let mut exporter = CsvExporter::create(&base).unwrap();
+        let distances: Vec<u32> = (100..116).collect();
+        exporter.writeTof(12.5, 4, &distances, &[80; 16]).unwrap();
+        exporter.writeImu(12.75, &ImuSample { timestamp: 7, accel: [0.5, -1.0, 1.0], gyro: [250.0, 0.0, -125.0] }).unwrap();
+        exporter.flush().unwrap();
+        let tof_csv = std::fs::read_to_string(withSuffix(&base, "_tof.csv")).unwrap();
+        let mut lines = tof_csv.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(header.len(), 2 + 2 * tof_csv_zones);
+        assert_eq!((header[2], header[65], header[66]), ("dist_0", "dist_63", "conf_0"));
+        let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(row.len(), header.len());
+        assert_eq!((row[0], row[1]), ("12.500000", "4x4"));
+        assert_eq!((row[2], row[17]), ("100", "115"));
+        assert!(row[18..66].iter().all(|cell| cell.is_empty()));
+        assert!(row[66..82].iter().all(|cell| *cell == "80"));
+        assert!(row[82..].iter().all(|cell| cell.is_empty()));
+        let imu_csv = std::fs::read_to_string(withSuffix(&base, "_imu.csv")).unwrap();
+        assert_eq!(imu_csv.lines().nth(1), Some("12.750000,7,0.5,-1,1,250,0,-125"));
+        let _ = std::fs::remove_file(withSuffix(&base, "_tof.csv"));
+        let _ = std::fs::remove_file(withSuffix(&base, "_imu.csv"));
+    }
+}
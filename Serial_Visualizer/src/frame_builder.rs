@@ -0,0 +1,139 @@
+use eframe::egui;
+use egui::{RichText, FontId, Color32};
+
+use crate::frame_decoder::buildRawFrame;
+use crate::packet_schema::PacketSchema;
+use crate::session::fromHex;
+
+/// Parse hex typed by hand: bytes may be separated by spaces or commas and prefixed with 0x.
+pub fn parseHexInput(text: &str) -> Result<Vec<u8>, String>
+{
+    let mut bytes = Vec::new();
+    for token in text.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty())
+    {
+        let digits = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")).unwrap_or(token);
+        let token_bytes = fromHex(digits).ok_or(format!("\"{}\" is not hex bytes", token))?;
+        bytes.extend(token_bytes);
+    }
+    Ok(bytes)
+}
+
+fn spacedHex(bytes: &[u8]) -> String
+{
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<String>>().join(" ")
+}
+
+/// Builds 0xFE frames from a type byte and a payload typed as hex or as schema fields, and
+/// sends arbitrary hex bytes.
+#[derive(Default)]
+pub struct FrameBuilder
+{
+    pub packet_type: u8,
+    /// Edit the payload through the schema layout for `packet_type` instead of as hex.
+    pub use_fields: bool,
+    payload_hex: String,
+    field_values: Vec<f64>,
+    raw_hex: String,
+}
+
+impl FrameBuilder
+{
+    fn buildPayload(&self, schema: &PacketSchema) -> Result<Vec<u8>, String>
+    {
+        if !self.use_fields
+        {
+            return parseHexInput(&self.payload_hex);
+        }
+        match schema.layout(self.packet_type)
+        {
+            Some(layout) => layout.encode(&self.field_values),
+            None => Err(format!("no schema layout for type {}", self.packet_type)),
+        }
+    }
+
+    /// The frame editor and the raw hex sender. Returns bytes to write to the port when a send button is clicked.
+    pub fn drawPanel(&mut self, ui: &mut egui::Ui, schema: &PacketSchema) -> Option<Vec<u8>>
+    {
+        let mut send = None;
+        ui.horizontal(|ui|{
+            ui.label("Type");
+            ui.add(egui::DragValue::new(&mut self.packet_type));
+            ui.radio_value(&mut self.use_fields, false, "Hex payload");
+            ui.radio_value(&mut self.use_fields, true, "Fields");
+            if !self.use_fields
+            {
+                ui.add(egui::TextEdit::singleline(&mut self.payload_hex).hint_text("payload hex, e.g. 08 d0 07").desired_width(300.0));
+            }
+        });
+        if self.use_fields
+        {
+            match schema.layout(self.packet_type).map(|layout| (layout, layout.slots()))
+            {
+                Some((layout, Ok(slots))) =>
+                {
+                    self.field_values.resize(slots.len(), 0.0);
+                    egui::Grid::new("frame-builder-fields").num_columns(2).show(ui, |ui|
+                    {
+                        for (slot, value) in slots.iter().zip(self.field_values.iter_mut())
+                        {
+                            ui.label(format!("{}.{}", layout.name, slot.name));
+                            ui.horizontal(|ui|{
+                                ui.add(egui::DragValue::new(value).speed(slot.field.scale));
+                                ui.label(&slot.field.unit);
+                            });
+                            ui.end_row();
+                        }
+                    });
+                },
+                Some((_, Err(e))) => { ui.label(e); },
+                None => { ui.label(format!("No schema layout for type {}, use a hex payload", self.packet_type)); },
+            }
+        }
+        //length and checksum are filled in, so the preview is exactly what gets sent
+        let raw_frame = self.buildPayload(schema).and_then(|payload| buildRawFrame(self.packet_type, &payload));
+        ui.horizontal(|ui|{
+            match &raw_frame
+            {
+                Ok(raw_frame) =>
+                {
+                    if ui.add(egui::Button::new("Send Frame")).clicked()
+                    {
+                        send = Some(raw_frame.clone());
+                    }
+                    ui.label(RichText::new(spacedHex(raw_frame)).font(FontId::monospace(12.0)));
+                },
+                Err(e) => { ui.label(RichText::new(e).color(Color32::RED)); },
+            }
+        });
+        ui.horizontal(|ui|{
+            ui.add(egui::TextEdit::singleline(&mut self.raw_hex).hint_text("raw hex bytes").desired_width(400.0));
+            match parseHexInput(&self.raw_hex)
+            {
+                Ok(bytes) =>
+                {
+                    if ui.add_enabled(!bytes.is_empty(), egui::Button::new("Send Hex")).clicked()
+                    {
+                        send = Some(bytes);
+                    }
+                },
+                Err(e) => { ui.label(RichText::new(e).color(Color32::RED)); },
+            }
+        });
+        send
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parses_loosely_formatted_hex()
+    {
+        assert_eq!(parseHexInput("fe 00,0x0A 0Xff d0d1").unwrap(), vec![0xFE, 0x00, 0x0A, 0xFF, 0xD0, 0xD1]);
+        assert!(parseHexInput("f").is_err());
+        assert!(parseHexInput("zz").is_err());
+        assert!(parseHexInput("  ").unwrap().is_empty());
+    }
+}
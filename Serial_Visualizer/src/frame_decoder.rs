@@ -0,0 +1,243 @@
+//Raw frame layout: 0xFE, three reserved bytes, payload length, packet type, payload, XOR checksum.
+//The checksum byte makes the XOR of the whole frame zero.
+pub const raw_frame_start: u8 = 0xFE;
+pub const raw_data_header: usize = 6;
+pub const raw_length_idx: usize = 4;
+pub const raw_type_idx: usize = 5;
+
+/// Something complete pulled out of the incoming byte stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameEvent
+{
+    Line(String),
+    /// Header, payload and checksum byte of a frame that passed `testChecksum`.
+    RawFrame(Vec<u8>),
+    /// A complete frame that failed `testChecksum` and was discarded.
+    ChecksumFailure(Vec<u8>),
+    /// Bytes of a line that was not valid UTF-8 and was discarded.
+    InvalidLine(Vec<u8>),
+}
+
+/// Splits a serial byte stream into text lines and 0xFE raw frames.
+///
+/// Bytes can arrive in chunks of any size; partial lines and frames are carried over to the
+/// next call to `push`. Frames with a bad checksum and lines that are not UTF-8 are reported
+/// rather than decoded, so callers can count what was lost.
+#[derive(Default)]
+pub struct FrameDecoder
+{
+    pending: Vec<u8>,
+    currently_reading_raw: bool,
+}
+
+pub fn testChecksum(raw_frame: &[u8]) -> bool
+{
+    let mut checksum: u8 = 0;
+    for dat in raw_frame
+    {
+        checksum ^= dat;
+    }
+    checksum == 0
+}
+
+/// Wrap a payload in a raw frame header and checksum, ready to send.
+pub fn buildRawFrame(packet_type: u8, payload: &[u8]) -> Result<Vec<u8>, String>
+{
+    let length = u8::try_from(payload.len()).map_err(|_| format!("payload is {} bytes, frames carry at most 255", payload.len()))?;
+    let mut raw_frame = vec![raw_frame_start, 0, 0, 0, length, packet_type];
+    raw_frame.extend_from_slice(payload);
+    let checksum = raw_frame.iter().fold(0, |acc, dat| acc ^ dat);
+    raw_frame.push(checksum);
+    Ok(raw_frame)
+}
+
+impl FrameDecoder
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Feed the next chunk of received bytes, returning every line and frame it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<FrameEvent>
+    {
+        let mut events = Vec::new();
+        for &byte in chunk
+        {
+            if self.currently_reading_raw
+            {
+                //raw payloads can contain any byte, so only the length field decides where the frame ends
+                self.pending.push(byte);
+                if self.pending.len() > raw_length_idx && self.pending.len() == self.pending[raw_length_idx] as usize + raw_data_header + 1
+                {
+                    let raw_frame = std::mem::take(&mut self.pending);
+                    if testChecksum(&raw_frame)
+                    {
+                        events.push(FrameEvent::RawFrame(raw_frame));
+                    }
+                    else
+                    {
+                        events.push(FrameEvent::ChecksumFailure(raw_frame));
+                    }
+                    self.currently_reading_raw = false;
+                }
+            }
+            else if byte == 0x0A || byte == 0x0D || byte == raw_frame_start
+            {
+                self.finishLine(&mut events);
+                if byte == raw_frame_start
+                {
+                    self.pending.push(byte);
+                    self.currently_reading_raw = true;
+                }
+            }
+            else
+            {
+                self.pending.push(byte);
+            }
+        }
+        events
+    }
+
+    fn finishLine(&mut self, events: &mut Vec<FrameEvent>)
+    {
+        if self.pending.is_empty()
+        {
+            return;
+        }
+        match String::from_utf8(std::mem::take(&mut self.pending))
+        {
+            Ok(full_str) => events.push(FrameEvent::Line(full_str)),
+            Err(e) => events.push(FrameEvent::InvalidLine(e.into_bytes())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn buildFrame(frame_type: u8, payload: &[u8]) -> Vec<u8>
+    {
+        buildRawFrame(frame_type, payload).unwrap()
+    }
+
+    fn line(text: &str) -> FrameEvent
+    {
+        FrameEvent::Line(text.to_string())
+    }
+
+    #[test]
+    fn checksum_accepts_zero_xor()
+    {
+        assert!(testChecksum(&buildFrame(0, &[1, 2, 3])));
+        assert!(!testChecksum(&[raw_frame_start, 0, 0, 0, 1, 0, 7, 0]));
+    }
+
+    #[test]
+    fn built_frames_fill_in_length_and_checksum()
+    {
+        assert_eq!(buildRawFrame(5, &[8, 0xD0, 0x07]).unwrap(), vec![raw_frame_start, 0, 0, 0, 3, 5, 8, 0xD0, 0x07, 0xFE ^ 3 ^ 5 ^ 8 ^ 0xD0 ^ 0x07]);
+        assert!(buildRawFrame(0, &[0; 256]).is_err());
+    }
+
+    #[test]
+    fn splits_lines_on_cr_and_lf()
+    {
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(b"hello\r\nworld\n"), vec![line("hello"), line("world")]);
+    }
+
+    #[test]
+    fn joins_lines_split_across_chunks()
+    {
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder.push(b"tof st").is_empty());
+        assert_eq!(decoder.push(b"arted\nnext"), vec![line("tof started")]);
+        assert_eq!(decoder.push(b"\n"), vec![line("next")]);
+    }
+
+    #[test]
+    fn reports_invalid_utf8_lines()
+    {
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(b"\xC3\x28\nok\n"), vec![FrameEvent::InvalidLine(vec![0xC3, 0x28]), line("ok")]);
+    }
+
+    #[test]
+    fn decodes_a_whole_frame()
+    {
+        let frame = buildFrame(0, &[1, 2, 3]);
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(&frame), vec![FrameEvent::RawFrame(frame)]);
+    }
+
+    #[test]
+    fn decodes_a_frame_split_byte_by_byte()
+    {
+        let frame = buildFrame(1, &[9, 8, 7, 6, 5, 4, 3, 2, 1]);
+        let mut decoder = FrameDecoder::new();
+        let mut events = Vec::new();
+        for byte in &frame
+        {
+            events.extend(decoder.push(&[*byte]));
+        }
+        assert_eq!(events, vec![FrameEvent::RawFrame(frame)]);
+    }
+
+    #[test]
+    fn decodes_a_frame_split_inside_the_header()
+    {
+        let frame = buildFrame(2, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder.push(&frame[..3]).is_empty());
+        assert!(decoder.push(&frame[3..10]).is_empty());
+        assert_eq!(decoder.push(&frame[10..]), vec![FrameEvent::RawFrame(frame)]);
+    }
+
+    #[test]
+    fn decodes_back_to_back_frames()
+    {
+        let first = buildFrame(0, &[1, 2, 3]);
+        let second = buildFrame(4, &[0x10; 48]);
+        let mut stream = first.clone();
+        stream.extend_from_slice(&second);
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(&stream), vec![FrameEvent::RawFrame(first), FrameEvent::RawFrame(second)]);
+    }
+
+    #[test]
+    fn keeps_line_bytes_inside_raw_payloads()
+    {
+        let frame = buildFrame(1, &[0x0A, 0x0D, 0xFE, 0x0A, 0x0D, 0xFE, 0, 0, 0]);
+        let mut stream = b"before\n".to_vec();
+        stream.extend_from_slice(&frame);
+        stream.extend_from_slice(b"after\n");
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(&stream), vec![line("before"), FrameEvent::RawFrame(frame), line("after")]);
+    }
+
+    #[test]
+    fn frame_start_ends_an_unterminated_line()
+    {
+        let frame = buildFrame(0, &[1, 2, 3]);
+        let mut stream = b"partial".to_vec();
+        stream.extend_from_slice(&frame);
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(&stream), vec![line("partial"), FrameEvent::RawFrame(frame)]);
+    }
+
+    #[test]
+    fn reports_frames_with_bad_checksums()
+    {
+        let mut corrupted = buildFrame(0, &[1, 2, 3]);
+        corrupted[7] ^= 0x40;
+        let good = buildFrame(0, &[4, 5, 6]);
+        let mut stream = corrupted.clone();
+        stream.extend_from_slice(&good);
+        stream.extend_from_slice(b"still here\n");
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.push(&stream), vec![FrameEvent::ChecksumFailure(corrupted), FrameEvent::RawFrame(good), line("still here")]);
+    }
+}
@@ -0,0 +1,177 @@
+use eframe::egui;
+use egui::{RichText, FontId};
+use std::collections::BTreeMap;
+
+use crate::frame_decoder::raw_type_idx;
+
+//frame rates are recomputed once this many seconds have passed
+const rate_window: f64 = 1.0;
+
+/// Counters for one raw frame type byte.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct TypeStats
+{
+    /// Frames that passed the checksum and were decoded.
+    pub frames: u64,
+    pub checksum_failures: u64,
+    /// Frames whose payload length the decoder for their type does not accept.
+    pub length_mismatches: u64,
+    /// Frames a registered handler rejected and no built-in decoder accepted.
+    pub decode_errors: u64,
+    /// Frames of a type with no built-in decoder and no registered handler.
+    pub unknown: u64,
+    /// Decoded frames per second over the last rate window.
+    pub rate: f64,
+    window_frames: u64,
+}
+
+/// Per-type frame counters and everything the decoder had to throw away, for diagnosing
+/// flaky cables and firmware bugs.
+#[derive(Default)]
+pub struct FrameStats
+{
+    types: BTreeMap<u8, TypeStats>,
+    /// Bytes of frames and lines that were received but never decoded.
+    pub discarded_bytes: u64,
+    pub invalid_lines: u64,
+    window_start: Option<f64>,
+}
+
+impl FrameStats
+{
+    pub fn typeStats(&self, packet_type: u8) -> Option<&TypeStats>
+    {
+        self.types.get(&packet_type)
+    }
+
+    pub fn recordFrame(&mut self, packet_type: u8)
+    {
+        let stats = self.types.entry(packet_type).or_default();
+        stats.frames += 1;
+        stats.window_frames += 1;
+    }
+
+    /// The type byte may itself be corrupted, but is usually right on a noisy line.
+    pub fn recordChecksumFailure(&mut self, raw_frame: &[u8])
+    {
+        self.types.entry(raw_frame[raw_type_idx]).or_default().checksum_failures += 1;
+        self.discarded_bytes += raw_frame.len() as u64;
+    }
+
+    pub fn recordLengthMismatch(&mut self, raw_frame: &[u8])
+    {
+        self.types.entry(raw_frame[raw_type_idx]).or_default().length_mismatches += 1;
+        self.discarded_bytes += raw_frame.len() as u64;
+    }
+
+    pub fn recordDecodeError(&mut self, raw_frame: &[u8])
+    {
+        self.types.entry(raw_frame[raw_type_idx]).or_default().decode_errors += 1;
+        self.discarded_bytes += raw_frame.len() as u64;
+    }
+
+    pub fn recordUnknownType(&mut self, raw_frame: &[u8])
+    {
+        self.types.entry(raw_frame[raw_type_idx]).or_default().unknown += 1;
+        self.discarded_bytes += raw_frame.len() as u64;
+    }
+
+    pub fn recordInvalidLine(&mut self, line: &[u8])
+    {
+        self.invalid_lines += 1;
+        self.discarded_bytes += line.len() as u64;
+    }
+
+    /// Recompute frame rates if a full window has passed since the last update. `now` is in seconds.
+    pub fn updateRates(&mut self, now: f64)
+    {
+        let window_start = *self.window_start.get_or_insert(now);
+        let elapsed = now - window_start;
+        if elapsed < rate_window
+        {
+            return;
+        }
+        for stats in self.types.values_mut()
+        {
+            stats.rate = stats.window_frames as f64 / elapsed;
+            stats.window_frames = 0;
+        }
+        self.window_start = Some(now);
+    }
+
+    /// Counter table with one row per type seen, plus a reset button.
+    pub fn drawPanel(&mut self, ui: &mut egui::Ui)
+    {
+        egui::CollapsingHeader::new("Frame statistics").id_source("frame-stats").show(ui, |ui|
+        {
+            egui::Grid::new("frame-stats-grid").num_columns(7).striped(true).show(ui, |ui|
+            {
+                for heading in ["type", "frames", "frames/s", "checksum failures", "length mismatches", "decode errors", "unknown type"]
+                {
+                    ui.label(RichText::new(heading).font(FontId::monospace(12.0)));
+                }
+                ui.end_row();
+                for (packet_type, stats) in &self.types
+                {
+                    ui.label(RichText::new(packet_type.to_string()).font(FontId::monospace(12.0)));
+                    ui.label(RichText::new(stats.frames.to_string()).font(FontId::monospace(12.0)));
+                    ui.label(RichText::new(format!("{:.1}", stats.rate)).font(FontId::monospace(12.0)));
+                    ui.label(RichText::new(stats.checksum_failures.to_string()).font(FontId::monospace(12.0)));
+                    ui.label(RichText::new(stats.length_mismatches.to_string()).font(FontId::monospace(12.0)));
+                    ui.label(RichText::new(stats.decode_errors.to_string()).font(FontId::monospace(12.0)));
+                    ui.label(RichText::new(stats.unknown.to_string()).font(FontId::monospace(12.0)));
+                    ui.end_row();
+                }
+            });
+            ui.horizontal(|ui|{
+                ui.label(format!("Discarded {} bytes, {} invalid lines", self.discarded_bytes, self.invalid_lines));
+                if ui.add(egui::Button::new("Reset Statistics")).clicked()
+                {
+                    *self = FrameStats::default();
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn counts_discarded_frames_per_type()
+    {
+        let mut stats = FrameStats::default();
+        let frame = [0xFE, 0, 0, 0, 1, 4, 0, 0];
+        stats.recordFrame(3);
+        stats.recordChecksumFailure(&frame);
+        stats.recordLengthMismatch(&frame);
+        stats.recordDecodeError(&frame);
+        stats.recordUnknownType(&[0xFE, 0, 0, 0, 0, 9, 0]);
+        stats.recordInvalidLine(&[0xC3, 0x28]);
+        assert_eq!(stats.typeStats(3).unwrap().frames, 1);
+        assert_eq!(stats.typeStats(4).unwrap().checksum_failures, 1);
+        assert_eq!(stats.typeStats(4).unwrap().length_mismatches, 1);
+        assert_eq!(stats.typeStats(4).unwrap().decode_errors, 1);
+        assert_eq!(stats.typeStats(9).unwrap().unknown, 1);
+        assert_eq!(stats.discarded_bytes, 8 + 8 + 8 + 7 + 2);
+    }
+
+    #[test]
+    fn rates_cover_the_last_window()
+    {
+        let mut stats = FrameStats::default();
+        stats.updateRates(100.0);
+        for _ in 0..30
+        {
+            stats.recordFrame(4);
+        }
+        stats.updateRates(100.5);
+        assert_eq!(stats.typeStats(4).unwrap().rate, 0.0);
+        stats.updateRates(102.0);
+        assert_eq!(stats.typeStats(4).unwrap().rate, 15.0);
+        stats.updateRates(103.0);
+        assert_eq!(stats.typeStats(4).unwrap().rate, 0.0);
+    }
+}
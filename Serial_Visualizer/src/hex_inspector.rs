@@ -0,0 +1,308 @@
+use eframe::egui;
+use egui::{RichText, FontId, Color32, Sense};
+use std::collections::VecDeque;
+
+use crate::frame_decoder::{raw_data_header, raw_length_idx, raw_type_idx, FrameDecoder, FrameEvent};
+use crate::packet_handler::{drawFieldGrid, drawOutput, PacketRegistry};
+use crate::packets::{decodePacket, PacketError};
+use crate::settings::ImuSettings;
+
+//only the most recent bytes are kept for display
+const inspector_capacity: usize = 8192;
+const bytes_per_row: usize = 16;
+
+/// What a received byte turned out to be once the stream around it was decoded.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ByteRole
+{
+    /// Text, or bytes of a frame that has not completed yet.
+    Text,
+    FrameStart,
+    Reserved,
+    Length,
+    Type,
+    Payload,
+    Checksum,
+}
+
+/// A complete frame seen in the byte stream, valid or not.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InspectedFrame
+{
+    /// Stream offset of the 0xFE start byte.
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+    pub checksum_ok: bool,
+}
+
+/// Hex dump of the received byte stream with frame headers and checksums highlighted.
+///
+/// Runs its own `FrameDecoder` byte by byte so it knows exactly where each frame starts and ends.
+#[derive(Default)]
+pub struct HexInspector
+{
+    decoder: FrameDecoder,
+    bytes: VecDeque<(u8, ByteRole)>,
+    /// Stream offset of the first byte still in `bytes`.
+    base_offset: u64,
+    frames: VecDeque<InspectedFrame>,
+    selected: Option<InspectedFrame>,
+    /// Bytes that arrived while paused, added to `base_offset` once reception resumes.
+    skipped: u64,
+    pub paused: bool,
+}
+
+fn roleAt(frame: &[u8], idx: usize) -> ByteRole
+{
+    match idx
+    {
+        0 => ByteRole::FrameStart,
+        raw_length_idx => ByteRole::Length,
+        raw_type_idx => ByteRole::Type,
+        _ if idx < raw_data_header => ByteRole::Reserved,
+        _ if idx == frame.len() - 1 => ByteRole::Checksum,
+        _ => ByteRole::Payload,
+    }
+}
+
+fn roleColor(role: ByteRole, checksum_ok: bool) -> Color32
+{
+    match role
+    {
+        ByteRole::Text => Color32::LIGHT_GRAY,
+        ByteRole::FrameStart => Color32::from_rgb(255, 90, 90),
+        ByteRole::Reserved => Color32::DARK_GRAY,
+        ByteRole::Length => Color32::YELLOW,
+        ByteRole::Type => Color32::from_rgb(80, 220, 255),
+        ByteRole::Payload => Color32::from_rgb(150, 170, 255),
+        ByteRole::Checksum if checksum_ok => Color32::GREEN,
+        ByteRole::Checksum => Color32::RED,
+    }
+}
+
+impl HexInspector
+{
+    /// Offset one past the last byte received.
+    pub fn endOffset(&self) -> u64
+    {
+        self.base_offset + self.bytes.len() as u64
+    }
+
+    /// Feed received bytes, in the order they arrived.
+    pub fn push(&mut self, chunk: &[u8])
+    {
+        if self.paused
+        {
+            //bytes skipped while paused would splice a half frame onto whatever arrives after resuming
+            self.decoder = FrameDecoder::default();
+            self.skipped += chunk.len() as u64;
+            return;
+        }
+        if self.skipped > 0
+        {
+            //the dump has a gap now, so restart it at the stream offset reception resumed from
+            self.base_offset = self.endOffset() + self.skipped;
+            self.skipped = 0;
+            self.bytes.clear();
+            self.frames.clear();
+        }
+        for &byte in chunk
+        {
+            self.bytes.push_back((byte, ByteRole::Text));
+            let frame = match self.decoder.push(&[byte]).pop()
+            {
+                Some(FrameEvent::RawFrame(frame)) => Some((frame, true)),
+                Some(FrameEvent::ChecksumFailure(frame)) => Some((frame, false)),
+                _ => None,
+            };
+            //the frame just completed ends on this byte, so its roles can be filled in backwards
+            if let Some((frame, checksum_ok)) = frame
+            {
+                let start = self.bytes.len().saturating_sub(frame.len());
+                for (idx, entry) in self.bytes.range_mut(start..).enumerate()
+                {
+                    entry.1 = roleAt(&frame, idx);
+                }
+                self.frames.push_back(InspectedFrame
+                {
+                    offset: self.endOffset() - frame.len() as u64,
+                    bytes: frame,
+                    checksum_ok,
+                });
+            }
+        }
+        while self.bytes.len() > inspector_capacity
+        {
+            self.bytes.pop_front();
+            self.base_offset += 1;
+        }
+        while self.frames.front().is_some_and(|frame| frame.offset < self.base_offset)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &InspectedFrame>
+    {
+        self.frames.iter()
+    }
+
+    /// The frame last clicked in the dump, if any.
+    pub fn selectedFrame(&self) -> Option<&InspectedFrame>
+    {
+        self.selected.as_ref()
+    }
+
+    pub fn clear(&mut self)
+    {
+        *self = HexInspector { paused: self.paused, ..Default::default() };
+    }
+
+    fn frameAt(&self, offset: u64) -> Option<&InspectedFrame>
+    {
+        self.frames.iter().find(|frame| frame.offset <= offset && offset < frame.offset + frame.bytes.len() as u64)
+    }
+
+    /// Offsets, hex bytes and printable text, 16 bytes per row. Clicking a frame byte selects the frame.
+    pub fn drawPanel(&mut self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|{
+            ui.checkbox(&mut self.paused, "Pause");
+            if ui.add(egui::Button::new("Clear")).clicked()
+            {
+                self.clear();
+            }
+            ui.label(format!("{} bytes received, {} frames shown", self.endOffset() + self.skipped, self.frames.len()));
+        });
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let first_row = (self.base_offset / bytes_per_row as u64) * bytes_per_row as u64;
+        let num_rows = (self.endOffset() - first_row).div_ceil(bytes_per_row as u64) as usize;
+        let mut clicked = None;
+        egui::ScrollArea::vertical().id_source("hex-inspector-scroll").stick_to_bottom(true).max_height(300.0).auto_shrink([false, true]).show_rows(ui, row_height, num_rows, |ui, rows|
+        {
+            let default_spacing = ui.spacing().item_spacing.x;
+            for row in rows
+            {
+                let row_offset = first_row + (row * bytes_per_row) as u64;
+                ui.horizontal(|ui|{
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    ui.label(RichText::new(format!("{:08x}  ", row_offset)).font(FontId::monospace(12.0)).color(Color32::GRAY));
+                    let mut text = String::new();
+                    for column in 0..bytes_per_row
+                    {
+                        let offset = row_offset + column as u64;
+                        let entry = offset.checked_sub(self.base_offset).and_then(|idx| self.bytes.get(idx as usize));
+                        let Some(&(byte, role)) = entry else
+                        {
+                            ui.label(RichText::new("   ").font(FontId::monospace(12.0)));
+                            text.push(' ');
+                            continue;
+                        };
+                        let frame = if role == ByteRole::Text { None } else { self.frameAt(offset) };
+                        let checksum_ok = frame.is_none_or(|frame| frame.checksum_ok);
+                        let mut label = RichText::new(format!("{:02x} ", byte)).font(FontId::monospace(12.0)).color(roleColor(role, checksum_ok));
+                        if frame.is_some() && frame == self.selected.as_ref()
+                        {
+                            label = label.background_color(Color32::from_gray(60));
+                        }
+                        if ui.add(egui::Label::new(label).sense(Sense::click())).clicked()
+                        {
+                            clicked = Some(frame.cloned());
+                        }
+                        text.push(if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' });
+                    }
+                    ui.label(RichText::new(format!(" {}", text)).font(FontId::monospace(12.0)));
+                    ui.spacing_mut().item_spacing.x = default_spacing;
+                });
+            }
+        });
+        if let Some(frame) = clicked
+        {
+            self.selected = frame;
+        }
+    }
+}
+
+/// Header fields of a selected frame and, if it passed its checksum, what the built-in decoder
+/// and the tab's registered handlers decode it to.
+pub fn drawFrameDetails(ui: &mut egui::Ui, frame: &InspectedFrame, imu_settings: &ImuSettings, registry: &mut PacketRegistry)
+{
+    let packet_type = frame.bytes[raw_type_idx];
+    ui.label(format!("Frame at offset {:08x}: type {}, length {}, checksum {}", frame.offset, packet_type,
+        frame.bytes[raw_length_idx], if frame.checksum_ok { "ok" } else { "FAILED" }));
+    if !frame.checksum_ok
+    {
+        return;
+    }
+    //own id scope, so the grids do not clash with the packet panels of the same type
+    ui.push_id("frame-details", |ui|
+    {
+        let handled = registry.decode(&frame.bytes);
+        match &handled
+        {
+            Some(Ok(output)) => drawOutput(ui, output),
+            Some(Err(e)) => { ui.label(e); },
+            None => {},
+        }
+        match decodePacket(&frame.bytes, imu_settings)
+        {
+            Ok(packet) => drawFieldGrid(ui, &packet.toDecoded()),
+            Err(PacketError::LengthMismatch { .. }) => { ui.label("Length does not match this type"); },
+            Err(PacketError::UnknownType(_)) if handled.is_none() => { ui.label("No decoder for this type"); },
+            Err(PacketError::UnknownType(_)) => {},
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn marks_frame_bytes_split_across_chunks()
+    {
+        let mut inspector = HexInspector::default();
+        let frame = [0xFE, 0, 0, 0, 1, 7, 0x42, 0xFE ^ 1 ^ 7 ^ 0x42];
+        inspector.push(b"hi\n");
+        inspector.push(&frame[..3]);
+        inspector.push(&frame[3..]);
+        let roles: Vec<ByteRole> = inspector.bytes.iter().map(|entry| entry.1).collect();
+        assert_eq!(roles, vec![ByteRole::Text, ByteRole::Text, ByteRole::Text, ByteRole::FrameStart,
+            ByteRole::Reserved, ByteRole::Reserved, ByteRole::Reserved, ByteRole::Length, ByteRole::Type,
+            ByteRole::Payload, ByteRole::Checksum]);
+        let frames: Vec<&InspectedFrame> = inspector.frames().collect();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].offset, 3);
+        assert!(frames[0].checksum_ok);
+    }
+
+    #[test]
+    fn pausing_drops_a_half_received_frame()
+    {
+        let mut inspector = HexInspector::default();
+        let frame = [0xFE, 0, 0, 0, 1, 7, 0x42, 0xFE ^ 1 ^ 7 ^ 0x42];
+        inspector.push(&frame[..5]);
+        inspector.paused = true;
+        inspector.push(&frame[5..]);
+        inspector.paused = false;
+        inspector.push(&frame[5..]);
+        inspector.push(&frame);
+        let frames: Vec<&InspectedFrame> = inspector.frames().collect();
+        assert_eq!(frames.len(), 1);
+        //offsets still count the 3 bytes skipped while paused
+        assert_eq!(frames[0].offset, 11);
+        assert!(frames[0].checksum_ok);
+        assert_eq!(inspector.endOffset(), 19);
+    }
+
+    #[test]
+    fn drops_frames_that_scroll_out()
+    {
+        let mut inspector = HexInspector::default();
+        inspector.push(&[0xFE, 0, 0, 0, 0, 3, 0xFE ^ 3]);
+        inspector.push(&vec![b'a'; inspector_capacity]);
+        assert_eq!(inspector.frames().count(), 0);
+        assert_eq!(inspector.endOffset(), 7 + inspector_capacity as u64);
+    }
+}
@@ -0,0 +1,82 @@
+use eframe::egui;
+use egui::{RichText, FontId};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use std::collections::VecDeque;
+
+use crate::packets::ImuSample;
+
+const imu_history_len: usize = 500;
+const axis_names: [&str; 3] = ["X", "Y", "Z"];
+
+/// Rolling window of recent IMU samples for the time-series plots.
+#[derive(Default)]
+pub struct ImuHistory
+{
+    samples: VecDeque<ImuSample>,
+}
+
+impl ImuHistory
+{
+    pub fn push(&mut self, sample: ImuSample)
+    {
+        //a timestamp going backwards means the 24 bit counter wrapped or the board reset
+        if self.samples.back().is_some_and(|last| sample.timestamp < last.timestamp)
+        {
+            self.samples.clear();
+        }
+        if self.samples.len() >= imu_history_len
+        {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn axisLine(&self, name: &str, value: impl Fn(&ImuSample) -> f32) -> Line
+    {
+        let points: PlotPoints = self.samples.iter().map(|sample| [sample.timestamp as f64, value(sample) as f64]).collect();
+        Line::new(points).name(name)
+    }
+}
+
+/// Live readouts for the latest sample, with accel and gyro plots keyed on the IMU timestamp.
+pub fn drawImuPanel(ui: &mut egui::Ui, imu_timestamp: u32, accel_matrix: &[f32], gyro_matrix: &[f32], history: &ImuHistory)
+{
+    ui.vertical(|ui|{
+        egui::Grid::new("imu-readouts").num_columns(4).show(ui, |ui|
+        {
+            ui.label(RichText::new(format!("IMU t = {}", imu_timestamp)).font(FontId::monospace(12.0)));
+            for name in axis_names
+            {
+                ui.label(RichText::new(name).font(FontId::monospace(12.0)));
+            }
+            ui.end_row();
+            ui.label(RichText::new("Accel (g)").font(FontId::monospace(12.0)));
+            for value in accel_matrix
+            {
+                ui.label(RichText::new(format!("{:>9.3}", value)).font(FontId::monospace(12.0)));
+            }
+            ui.end_row();
+            ui.label(RichText::new("Gyro (dps)").font(FontId::monospace(12.0)));
+            for value in gyro_matrix
+            {
+                ui.label(RichText::new(format!("{:>9.2}", value)).font(FontId::monospace(12.0)));
+            }
+            ui.end_row();
+        });
+        let plot_width = ui.available_width().min(760.0);
+        Plot::new("accel-plot").height(90.0).width(plot_width).legend(Legend::default()).show(ui, |plot_ui|
+        {
+            for (axis, name) in axis_names.iter().enumerate()
+            {
+                plot_ui.line(history.axisLine(&format!("Accel {}", name), |sample| sample.accel[axis]));
+            }
+        });
+        Plot::new("gyro-plot").height(90.0).width(plot_width).legend(Legend::default()).show(ui, |plot_ui|
+        {
+            for (axis, name) in axis_names.iter().enumerate()
+            {
+                plot_ui.line(history.axisLine(&format!("Gyro {}", name), |sample| sample.gyro[axis]));
+            }
+        });
+    });
+}
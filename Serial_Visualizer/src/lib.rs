@@ -0,0 +1,29 @@
+#![allow(non_snake_case, non_upper_case_globals)]
+
+//! Frame decoding, packet model and serial transport shared by the GUI and headless binaries.
+//! The egui panels are behind the default `gui` feature.
+
+#[cfg(feature = "gui")]
+pub mod app;
+pub mod command_history;
+pub mod csv_export;
+#[cfg(feature = "gui")]
+pub mod frame_builder;
+pub mod frame_decoder;
+#[cfg(feature = "gui")]
+pub mod frame_stats;
+#[cfg(feature = "gui")]
+pub mod hex_inspector;
+#[cfg(feature = "gui")]
+pub mod imu_panel;
+pub mod macros;
+pub mod packet_handler;
+pub mod packet_schema;
+pub mod packets;
+pub mod reconnect;
+pub mod replay;
+pub mod serial_reader;
+pub mod session;
+pub mod settings;
+pub mod simulator;
+pub mod transport;
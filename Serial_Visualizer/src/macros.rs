@@ -0,0 +1,255 @@
+#[cfg(feature = "gui")]
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::settings::LineEnding;
+
+/// Command buttons are read from this file in the working directory unless the settings name another.
+pub const default_macro_file: &str = "Serial_Visualizer_macros.toml";
+
+/// A named button that sends one or more commands.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CommandMacro
+{
+    pub name: String,
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// Wait between consecutive commands, for firmware that needs time to act on each one.
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MacroSet
+{
+    #[serde(default, rename = "macro")]
+    pub macros: Vec<CommandMacro>,
+}
+
+/// The commands that used to be hard-coded buttons.
+impl Default for MacroSet
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            macros: vec![
+                CommandMacro
+                {
+                    name: "Enable Serialization".to_string(),
+                    commands: vec!["uart set_serialize true".to_string()],
+                    line_ending: LineEnding::Lf,
+                    delay_ms: 0,
+                },
+                CommandMacro
+                {
+                    name: "Start ToF Measurements".to_string(),
+                    commands: vec!["tof start_measurements".to_string()],
+                    line_ending: LineEnding::Lf,
+                    delay_ms: 0,
+                },
+            ],
+        }
+    }
+}
+
+impl MacroSet
+{
+    /// A missing file gives the default buttons; a broken one is reported.
+    pub fn loadOrDefault(path: &str) -> Self
+    {
+        let contents = match std::fs::read_to_string(path)
+        {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+        match toml::from_str(&contents)
+        {
+            Ok(macros) => macros,
+            Err(e) =>
+            {
+                eprintln!("Failed to parse \"{}\", using default macros. Error: {}", path, e);
+                Self::default()
+            },
+        }
+    }
+
+    pub fn save(&self, path: &str)
+    {
+        let mut macros = self.clone();
+        for command_macro in &mut macros.macros
+        {
+            command_macro.commands.retain(|command| !command.is_empty());
+        }
+        let contents = match toml::to_string_pretty(&macros)
+        {
+            Ok(contents) => contents,
+            Err(e) =>
+            {
+                eprintln!("Failed to serialize macros. Error: {}", e);
+                return;
+            },
+        };
+        if let Err(e) = std::fs::write(Path::new(path), contents)
+        {
+            eprintln!("Failed to write \"{}\". Error: {}", path, e);
+        }
+    }
+
+    /// One row per macro: name, commands (one per line), line ending, delay and remove.
+    #[cfg(feature = "gui")]
+    pub fn drawEditor(&mut self, ui: &mut egui::Ui)
+    {
+        let mut remove = None;
+        for (idx, command_macro) in self.macros.iter_mut().enumerate()
+        {
+            ui.horizontal(|ui|{
+                ui.add(egui::TextEdit::singleline(&mut command_macro.name).hint_text("name").desired_width(160.0));
+                let mut commands = command_macro.commands.join("\n");
+                if ui.add(egui::TextEdit::multiline(&mut commands).hint_text("one command per line").desired_rows(1).desired_width(300.0)).changed()
+                {
+                    //blank lines stay while editing so Enter at the end starts a new command; sending and saving skip them
+                    command_macro.commands = commands.split('\n').map(|line| line.to_string()).collect();
+                }
+                command_macro.line_ending.drawSelector(ui, ("macro-line-ending", idx));
+                ui.add(egui::DragValue::new(&mut command_macro.delay_ms).clamp_range(0..=60_000).suffix(" ms"));
+                if ui.add(egui::Button::new("Remove")).clicked()
+                {
+                    remove = Some(idx);
+                }
+            });
+        }
+        if let Some(idx) = remove
+        {
+            self.macros.remove(idx);
+        }
+        if ui.add(egui::Button::new("Add Macro")).clicked()
+        {
+            self.macros.push(CommandMacro
+            {
+                name: "New Macro".to_string(),
+                commands: Vec::new(),
+                line_ending: LineEnding::Lf,
+                delay_ms: 0,
+            });
+        }
+    }
+}
+
+/// Commands waiting for their macro's delay to pass before being written.
+#[derive(Default)]
+pub struct MacroQueue
+{
+    pending: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl MacroQueue
+{
+    /// Queue every command of a macro. Macros started while another is running go after it.
+    pub fn start(&mut self, command_macro: &CommandMacro, now: Instant)
+    {
+        let delay = Duration::from_millis(command_macro.delay_ms);
+        let mut send_at = match self.pending.back()
+        {
+            Some((last, _)) => (*last + delay).max(now),
+            None => now,
+        };
+        for command in command_macro.commands.iter().filter(|command| !command.is_empty())
+        {
+            let mut bytes = command.clone().into_bytes();
+            bytes.extend_from_slice(command_macro.line_ending.text().as_bytes());
+            self.pending.push_back((send_at, bytes));
+            send_at += delay;
+        }
+    }
+
+    /// Remove and return every command due by `now`, oldest first.
+    pub fn takeDue(&mut self, now: Instant) -> Vec<Vec<u8>>
+    {
+        let mut due = Vec::new();
+        while self.pending.front().is_some_and(|(send_at, _)| *send_at <= now)
+        {
+            if let Some((_, bytes)) = self.pending.pop_front()
+            {
+                due.push(bytes);
+            }
+        }
+        due
+    }
+
+    pub fn nextDue(&self) -> Option<Instant>
+    {
+        self.pending.front().map(|(send_at, _)| *send_at)
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn spaces_commands_by_the_macro_delay()
+    {
+        let command_macro = CommandMacro
+        {
+            name: "init".to_string(),
+            commands: vec!["a".to_string(), "b".to_string()],
+            line_ending: LineEnding::CrLf,
+            delay_ms: 100,
+        };
+        let now = Instant::now();
+        let mut queue = MacroQueue::default();
+        queue.start(&command_macro, now);
+        assert_eq!(queue.takeDue(now), vec![b"a\r\n".to_vec()]);
+        assert_eq!(queue.nextDue(), Some(now + Duration::from_millis(100)));
+        assert!(queue.takeDue(now + Duration::from_millis(99)).is_empty());
+        assert_eq!(queue.takeDue(now + Duration::from_millis(100)), vec![b"b\r\n".to_vec()]);
+        assert_eq!(queue.nextDue(), None);
+    }
+
+    #[test]
+    fn skips_blank_lines_left_by_the_editor()
+    {
+        let command_macro = CommandMacro
+        {
+            name: "init".to_string(),
+            commands: vec!["a".to_string(), "".to_string(), "b".to_string(), "".to_string()],
+            line_ending: LineEnding::Lf,
+            delay_ms: 0,
+        };
+        let now = Instant::now();
+        let mut queue = MacroQueue::default();
+        queue.start(&command_macro, now);
+        assert_eq!(queue.takeDue(now), vec![b"a\n".to_vec(), b"b\n".to_vec()]);
+        let path = std::env::temp_dir().join(format!("sv_macros_blank_{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        MacroSet { macros: vec![command_macro] }.save(path);
+        assert_eq!(MacroSet::loadOrDefault(path).macros[0].commands, vec!["a", "b"]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn parses_macro_files()
+    {
+        let macros: MacroSet = toml::from_str(r#"
+            [[macro]]
+            name = "Calibrate"
+            commands = ["imu stop", "imu calibrate"]
+            line_ending = "crlf"
+            delay_ms = 250
+        "#).unwrap();
+        assert_eq!(macros.macros[0].line_ending, LineEnding::CrLf);
+        assert_eq!(macros.macros[0].commands.len(), 2);
+        assert_eq!(macros.macros[0].delay_ms, 250);
+    }
+}
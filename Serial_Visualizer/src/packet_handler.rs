@@ -0,0 +1,236 @@
+#[cfg(feature = "gui")]
+use eframe::egui::{self, RichText, FontId};
+use std::collections::BTreeMap;
+
+use crate::frame_decoder::raw_type_idx;
+use crate::packet_schema::DecodedPacket;
+
+/// Draws a custom view for one decoded packet.
+#[cfg(feature = "gui")]
+pub type PanelRenderer = Box<dyn Fn(&mut egui::Ui)>;
+
+/// What a handler produced for the latest frame of its type.
+pub struct HandlerOutput
+{
+    pub value: DecodedPacket,
+    /// Custom view for `value`. Without one, the fields are shown as a name/value grid.
+    #[cfg(feature = "gui")]
+    pub panel: Option<PanelRenderer>,
+}
+
+impl HandlerOutput
+{
+    /// Output shown as a name/value grid of its fields.
+    pub fn fields(value: DecodedPacket) -> Self
+    {
+        Self
+        {
+            value,
+            #[cfg(feature = "gui")]
+            panel: None,
+        }
+    }
+}
+
+/// Decoder for one raw frame type, registered with a `PacketRegistry`.
+///
+/// Implement this to add views for new packet types (motor encoders, battery, ...) without
+/// touching the built-in IMU and ToF handling.
+pub trait PacketHandler
+{
+    /// Raw frame type byte this handler decodes.
+    fn packetType(&self) -> u8;
+
+    /// Decode a checksum-valid frame of this handler's type.
+    fn handlePacket(&mut self, raw_frame: &[u8]) -> Result<HandlerOutput, String>;
+}
+
+/// Dispatches raw frames to registered handlers by type byte and keeps their latest output.
+///
+/// Handlers registered in code take priority over the layouts of the packet schema file, so
+/// reloading the schema never replaces them.
+#[derive(Default)]
+pub struct PacketRegistry
+{
+    handlers: BTreeMap<u8, Box<dyn PacketHandler>>,
+    schema_handlers: BTreeMap<u8, Box<dyn PacketHandler>>,
+    latest: BTreeMap<u8, HandlerOutput>,
+}
+
+impl PacketRegistry
+{
+    /// Register a handler, replacing and returning any handler already registered for its type.
+    pub fn register(&mut self, handler: Box<dyn PacketHandler>) -> Option<Box<dyn PacketHandler>>
+    {
+        self.handlers.insert(handler.packetType(), handler)
+    }
+
+    pub fn unregister(&mut self, packet_type: u8) -> Option<Box<dyn PacketHandler>>
+    {
+        self.latest.remove(&packet_type);
+        self.handlers.remove(&packet_type)
+    }
+
+    /// Replace the handlers loaded from the packet schema, dropping the latest output of types
+    /// only the old schema handled.
+    pub fn setSchemaHandlers(&mut self, schema_handlers: Vec<Box<dyn PacketHandler>>)
+    {
+        for packet_type in std::mem::take(&mut self.schema_handlers).into_keys()
+        {
+            if !self.handlers.contains_key(&packet_type)
+            {
+                self.latest.remove(&packet_type);
+            }
+        }
+        self.schema_handlers = schema_handlers.into_iter().map(|handler| (handler.packetType(), handler)).collect();
+    }
+
+    /// Decode a frame with the handler for its type without keeping the output, e.g. to inspect
+    /// an older frame. Returns None if no handler is registered.
+    pub fn decode(&mut self, raw_frame: &[u8]) -> Option<Result<HandlerOutput, String>>
+    {
+        let packet_type = raw_frame[raw_type_idx];
+        let handler = self.handlers.get_mut(&packet_type).or(self.schema_handlers.get_mut(&packet_type))?;
+        Some(handler.handlePacket(raw_frame))
+    }
+
+    /// Hand a frame to the handler for its type. Returns None if no handler is registered,
+    /// otherwise whether the handler could decode the frame.
+    pub fn dispatch(&mut self, raw_frame: &[u8]) -> Option<Result<(), String>>
+    {
+        let packet_type = raw_frame[raw_type_idx];
+        let output = self.decode(raw_frame)?;
+        Some(output.map(|output| { self.latest.insert(packet_type, output); }))
+    }
+
+    pub fn clearLatest(&mut self)
+    {
+        self.latest.clear();
+    }
+
+    /// One collapsible section per packet type received so far.
+    #[cfg(feature = "gui")]
+    pub fn drawPanels(&self, ui: &mut egui::Ui)
+    {
+        for (packet_type, output) in &self.latest
+        {
+            egui::CollapsingHeader::new(format!("{} (type {})", output.value.name, packet_type))
+                .id_source(("packet-panel", *packet_type))
+                .default_open(true)
+                .show(ui, |ui| drawOutput(ui, output));
+        }
+    }
+}
+
+/// The handler's custom panel, or its fields as a name/value grid.
+#[cfg(feature = "gui")]
+pub fn drawOutput(ui: &mut egui::Ui, output: &HandlerOutput)
+{
+    match &output.panel
+    {
+        Some(panel) => panel(ui),
+        None => drawFieldGrid(ui, &output.value),
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn drawFieldGrid(ui: &mut egui::Ui, packet: &DecodedPacket)
+{
+    egui::Grid::new(("packet-fields", packet.type_id)).num_columns(2).show(ui, |ui|
+    {
+        for field in &packet.fields
+        {
+            ui.label(RichText::new(&field.name).font(FontId::monospace(12.0)));
+            ui.label(RichText::new(format!("{:>12.4} {}", field.value, field.unit)).font(FontId::monospace(12.0)));
+            ui.end_row();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::frame_decoder::buildRawFrame;
+
+    /// Handler that only reports its name, to tell which one a frame went to.
+    struct NamedHandler
+    {
+        packet_type: u8,
+        name: &'static str,
+    }
+
+    impl PacketHandler for NamedHandler
+    {
+        fn packetType(&self) -> u8
+        {
+            self.packet_type
+        }
+
+        fn handlePacket(&mut self, _raw_frame: &[u8]) -> Result<HandlerOutput, String>
+        {
+            Ok(HandlerOutput::fields(DecodedPacket { type_id: self.packet_type, name: self.name.to_string(), fields: vec![] }))
+        }
+    }
+
+    fn named(packet_type: u8, name: &'static str) -> Box<dyn PacketHandler>
+    {
+        Box::new(NamedHandler { packet_type, name })
+    }
+
+    fn latestName(registry: &PacketRegistry, packet_type: u8) -> Option<&str>
+    {
+        registry.latest.get(&packet_type).map(|output| output.value.name.as_str())
+    }
+
+    #[test]
+    fn dispatches_by_type_byte()
+    {
+        let mut registry = PacketRegistry::default();
+        registry.register(named(7, "battery"));
+        registry.register(named(8, "encoders"));
+        assert_eq!(registry.dispatch(&buildRawFrame(8, &[1, 2]).unwrap()), Some(Ok(())));
+        assert_eq!(latestName(&registry, 8), Some("encoders"));
+        assert_eq!(latestName(&registry, 7), None);
+        assert_eq!(registry.dispatch(&buildRawFrame(9, &[1, 2]).unwrap()), None);
+    }
+
+    #[test]
+    fn decoding_leaves_the_latest_output_alone()
+    {
+        let mut registry = PacketRegistry::default();
+        registry.register(named(7, "battery"));
+        let output = registry.decode(&buildRawFrame(7, &[]).unwrap()).unwrap().unwrap();
+        assert_eq!(output.value.name, "battery");
+        assert_eq!(latestName(&registry, 7), None);
+    }
+
+    #[test]
+    fn registering_a_type_again_replaces_the_handler()
+    {
+        let mut registry = PacketRegistry::default();
+        assert!(registry.register(named(7, "battery")).is_none());
+        let replaced = registry.register(named(7, "battery v2")).unwrap();
+        assert_eq!(replaced.packetType(), 7);
+        registry.dispatch(&buildRawFrame(7, &[]).unwrap());
+        assert_eq!(latestName(&registry, 7), Some("battery v2"));
+    }
+
+    #[test]
+    fn registered_handlers_survive_a_schema_reload()
+    {
+        let mut registry = PacketRegistry::default();
+        registry.register(named(7, "battery plugin"));
+        registry.setSchemaHandlers(vec![named(7, "battery schema"), named(8, "encoders schema")]);
+        registry.dispatch(&buildRawFrame(7, &[]).unwrap());
+        registry.dispatch(&buildRawFrame(8, &[]).unwrap());
+        assert_eq!(latestName(&registry, 7), Some("battery plugin"));
+        assert_eq!(latestName(&registry, 8), Some("encoders schema"));
+        registry.setSchemaHandlers(vec![named(9, "status schema")]);
+        assert_eq!(latestName(&registry, 7), Some("battery plugin"));
+        assert_eq!(latestName(&registry, 8), None);
+        assert!(registry.dispatch(&buildRawFrame(7, &[]).unwrap()).is_some());
+        assert!(registry.dispatch(&buildRawFrame(8, &[]).unwrap()).is_none());
+        assert!(registry.dispatch(&buildRawFrame(9, &[]).unwrap()).is_some());
+    }
+}
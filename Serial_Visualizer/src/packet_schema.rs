@@ -0,0 +1,349 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::frame_decoder::{raw_data_header, raw_length_idx, raw_type_idx};
+use crate::packet_handler::{HandlerOutput, PacketHandler};
+
+/// Packet layouts are read from this file in the working directory unless the settings name another.
+pub const default_schema_file: &str = "Serial_Visualizer_packets.toml";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness
+{
+    #[default]
+    Little,
+    Big,
+}
+
+fn defaultWidth() -> usize { 1 }
+fn defaultScale() -> f64 { 1.0 }
+fn defaultCount() -> usize { 1 }
+
+/// One named integer in a packet payload.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FieldLayout
+{
+    pub name: String,
+    /// Width in bytes, 1 to 4.
+    #[serde(default = "defaultWidth")]
+    pub width: usize,
+    #[serde(default)]
+    pub signed: bool,
+    #[serde(default)]
+    pub endian: Endianness,
+    /// Raw integer values are multiplied by this.
+    #[serde(default = "defaultScale")]
+    pub scale: f64,
+    /// Byte offset into the payload. Defaults to straight after the previous field.
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// Repeat the field this many times back to back, named `name[0]`, `name[1]`, ...
+    #[serde(default = "defaultCount")]
+    pub count: usize,
+    #[serde(default)]
+    pub unit: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PacketLayout
+{
+    /// Matched against the type byte of the raw frame header.
+    pub type_id: u8,
+    pub name: String,
+    /// Expected payload length. Frames of any other length are rejected when set.
+    #[serde(default)]
+    pub length: Option<usize>,
+    #[serde(default, rename = "field")]
+    pub fields: Vec<FieldLayout>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct PacketSchema
+{
+    #[serde(default, rename = "packet")]
+    pub packets: Vec<PacketLayout>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct DecodedField
+{
+    pub name: String,
+    pub value: f64,
+    pub unit: String,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct DecodedPacket
+{
+    pub type_id: u8,
+    pub name: String,
+    pub fields: Vec<DecodedField>,
+}
+
+fn readInteger(bytes: &[u8], signed: bool, endian: Endianness) -> f64
+{
+    let mut raw: u32 = 0;
+    for (idx, byte) in bytes.iter().enumerate()
+    {
+        let shift = match endian
+        {
+            Endianness::Little => 8 * idx,
+            Endianness::Big => 8 * (bytes.len() - 1 - idx),
+        };
+        raw |= (*byte as u32) << shift;
+    }
+    if signed
+    {
+        //sign extend from the top bit of the field
+        let unused_bits = 32 - 8 * bytes.len() as u32;
+        (((raw << unused_bits) as i32) >> unused_bits) as f64
+    }
+    else
+    {
+        raw as f64
+    }
+}
+
+/// One value of a layout, with repeated fields expanded.
+pub struct FieldSlot<'a>
+{
+    /// `name`, or `name[i]` for repeated fields.
+    pub name: String,
+    /// Byte offset into the payload.
+    pub start: usize,
+    pub field: &'a FieldLayout,
+}
+
+fn writeInteger(bytes: &mut [u8], raw: i64, endian: Endianness)
+{
+    let width = bytes.len();
+    for (idx, byte) in bytes.iter_mut().enumerate()
+    {
+        let shift = match endian
+        {
+            Endianness::Little => 8 * idx,
+            Endianness::Big => 8 * (width - 1 - idx),
+        };
+        *byte = (raw >> shift) as u8;
+    }
+}
+
+impl PacketLayout
+{
+    /// Every value in payload order, with offsets resolved.
+    pub fn slots(&self) -> Result<Vec<FieldSlot<'_>>, String>
+    {
+        let mut slots = Vec::new();
+        let mut next_offset = 0;
+        for field in &self.fields
+        {
+            if field.width == 0 || field.width > 4
+            {
+                return Err(format!("{}.{} has unsupported width {}", self.name, field.name, field.width));
+            }
+            let start = field.offset.unwrap_or(next_offset);
+            for repeat in 0..field.count
+            {
+                let name = if field.count == 1 { field.name.clone() } else { format!("{}[{}]", field.name, repeat) };
+                slots.push(FieldSlot { name, start: start + repeat * field.width, field });
+            }
+            next_offset = start + field.count * field.width;
+        }
+        Ok(slots)
+    }
+
+    /// Decode the payload of a checksum-valid raw frame into named, scaled values.
+    pub fn decode(&self, raw_frame: &[u8]) -> Result<DecodedPacket, String>
+    {
+        let payload_len = raw_frame[raw_length_idx] as usize;
+        let payload = &raw_frame[raw_data_header..raw_data_header + payload_len];
+        if let Some(length) = self.length
+        {
+            if payload_len != length
+            {
+                return Err(format!("{} expects {} payload bytes, got {}", self.name, length, payload_len));
+            }
+        }
+        let mut fields = Vec::new();
+        for slot in self.slots()?
+        {
+            let Some(bytes) = payload.get(slot.start..slot.start + slot.field.width) else
+            {
+                return Err(format!("{}.{} runs past the {} byte payload", self.name, slot.field.name, payload_len));
+            };
+            fields.push(DecodedField
+            {
+                name: slot.name,
+                value: readInteger(bytes, slot.field.signed, slot.field.endian) * slot.field.scale,
+                unit: slot.field.unit.clone(),
+            });
+        }
+        Ok(DecodedPacket
+        {
+            type_id: raw_frame[raw_type_idx],
+            name: self.name.clone(),
+            fields,
+        })
+    }
+
+    /// Build a payload from one scaled value per slot, the inverse of `decode`.
+    pub fn encode(&self, values: &[f64]) -> Result<Vec<u8>, String>
+    {
+        let slots = self.slots()?;
+        if values.len() != slots.len()
+        {
+            return Err(format!("{} has {} fields, got {} values", self.name, slots.len(), values.len()));
+        }
+        let end = slots.iter().map(|slot| slot.start + slot.field.width).max().unwrap_or(0);
+        let mut payload = vec![0; self.length.unwrap_or(end).max(end)];
+        for (slot, value) in slots.iter().zip(values)
+        {
+            let raw = (value / slot.field.scale).round() as i64;
+            let bits = 8 * slot.field.width as u32;
+            let (min, max) = if slot.field.signed { (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1) } else { (0, (1i64 << bits) - 1) };
+            if raw < min || raw > max
+            {
+                return Err(format!("{} = {} does not fit in {} bytes", slot.name, value, slot.field.width));
+            }
+            writeInteger(&mut payload[slot.start..slot.start + slot.field.width], raw, slot.field.endian);
+        }
+        Ok(payload)
+    }
+}
+
+impl PacketSchema
+{
+    /// Parse a schema file, as JSON if it ends in `.json` and as TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, String>
+    {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        if path.extension().is_some_and(|ext| ext == "json")
+        {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        }
+        else
+        {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        }
+    }
+
+    /// A missing schema file just means no extra packet types; a broken one is reported.
+    pub fn loadOrDefault(path: &str) -> Self
+    {
+        if !Path::new(path).exists()
+        {
+            return Self::default();
+        }
+        match Self::load(Path::new(path))
+        {
+            Ok(schema) => schema,
+            Err(e) =>
+            {
+                eprintln!("Failed to load packet schema \"{}\". Error: {}", path, e);
+                Self::default()
+            },
+        }
+    }
+
+    pub fn layout(&self, type_id: u8) -> Option<&PacketLayout>
+    {
+        self.packets.iter().find(|packet| packet.type_id == type_id)
+    }
+}
+
+/// Schema layouts are packet handlers like any other, shown with the generic field grid.
+impl PacketHandler for PacketLayout
+{
+    fn packetType(&self) -> u8
+    {
+        self.type_id
+    }
+
+    fn handlePacket(&mut self, raw_frame: &[u8]) -> Result<HandlerOutput, String>
+    {
+        Ok(HandlerOutput::fields(self.decode(raw_frame)?))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::frame_decoder::buildRawFrame;
+
+    const battery_schema: &str = r#"
+        [[packet]]
+        type_id = 7
+        name = "battery"
+        length = 7
+
+        [[packet.field]]
+        name = "timestamp"
+        width = 3
+
+        [[packet.field]]
+        name = "current"
+        width = 2
+        signed = true
+        scale = 0.01
+        unit = "A"
+
+        [[packet.field]]
+        name = "voltage"
+        width = 2
+        endian = "big"
+        scale = 0.001
+        unit = "V"
+    "#;
+
+    #[test]
+    fn decodes_packed_fields()
+    {
+        let schema: PacketSchema = toml::from_str(battery_schema).unwrap();
+        let layout = schema.layout(7).unwrap();
+        let decoded = layout.decode(&buildRawFrame(7, &[0x01, 0x02, 0x03, 0x9C, 0xFF, 0x2E, 0xE0]).unwrap()).unwrap();
+        let values: Vec<f64> = decoded.fields.iter().map(|field| field.value).collect();
+        assert_eq!(values[0], 0x030201 as f64);
+        assert!((values[1] - -1.0).abs() < 1e-9);
+        assert!((values[2] - 12.0).abs() < 1e-9);
+        assert_eq!(decoded.fields[2].unit, "V");
+    }
+
+    #[test]
+    fn encodes_what_it_decodes()
+    {
+        let schema: PacketSchema = toml::from_str(battery_schema).unwrap();
+        let layout = schema.layout(7).unwrap();
+        let payload = layout.encode(&[0x030201 as f64, -1.0, 12.0]).unwrap();
+        assert_eq!(payload, vec![0x01, 0x02, 0x03, 0x9C, 0xFF, 0x2E, 0xE0]);
+        assert!(layout.encode(&[0.0, 400.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_length()
+    {
+        let schema: PacketSchema = toml::from_str(battery_schema).unwrap();
+        assert!(schema.layout(7).unwrap().decode(&buildRawFrame(7, &[0; 6]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn expands_repeated_fields_from_json()
+    {
+        let schema: PacketSchema = serde_json::from_str(r#"{"packet": [{"type_id": 9, "name": "encoders",
+            "field": [{"name": "ticks", "width": 2, "signed": true, "count": 2, "offset": 1}]}]}"#).unwrap();
+        let decoded = schema.layout(9).unwrap().decode(&buildRawFrame(9, &[0xAA, 0x01, 0x00, 0xFF, 0xFF]).unwrap()).unwrap();
+        assert_eq!(decoded.fields[0].name, "ticks[0]");
+        assert_eq!(decoded.fields[0].value, 1.0);
+        assert_eq!(decoded.fields[1].name, "ticks[1]");
+        assert_eq!(decoded.fields[1].value, -1.0);
+    }
+
+    #[test]
+    fn example_schema_leaves_built_in_types_alone()
+    {
+        let schema: PacketSchema = toml::from_str(include_str!("../packets.example.toml")).unwrap();
+        assert!(!schema.packets.is_empty());
+        assert!(schema.packets.iter().all(|layout| layout.type_id > crate::packets::imu_config_type));
+    }
+}
@@ -0,0 +1,229 @@
+use serde::Serialize;
+
+use crate::frame_decoder::{raw_data_header, raw_length_idx, raw_type_idx};
+use crate::packet_schema::{DecodedField, DecodedPacket};
+use crate::settings::ImuSettings;
+
+//Built-in packet types, matched against the type byte of the raw frame header.
+pub const imu_timestamp_type: u8 = 0;
+pub const accel_type: u8 = 1;
+pub const gyro_type: u8 = 2;
+pub const imu_type: u8 = 3;
+pub const tof_type: u8 = 4;
+/// Full-scale ranges the firmware configured: accel in g (u8), gyro in dps (u16).
+pub const imu_config_type: u8 = 5;
+
+/// A decoded built-in packet. Serializes with its kind in a "packet" field, e.g. `{"packet":"tof",...}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "packet", rename_all = "snake_case")]
+pub enum Packet
+{
+    ImuTimestamp { timestamp: u32 },
+    Accel { timestamp: u32, accel: [f32; 3] },
+    Gyro { timestamp: u32, gyro: [f32; 3] },
+    Imu { timestamp: u32, accel: [f32; 3], gyro: [f32; 3] },
+    /// Zones are row-major, `resolution` x `resolution`.
+    Tof { resolution: usize, distances: Vec<u32>, confidences: Vec<u8> },
+    ImuConfig(ImuSettings),
+}
+
+/// One combined IMU reading in g and dps, as plotted and exported to CSV.
+#[derive(Clone, Copy)]
+pub struct ImuSample
+{
+    pub timestamp: u32,
+    pub accel: [f32; 3],
+    pub gyro: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketError
+{
+    /// The payload length in the header does not match what the packet type requires.
+    LengthMismatch { packet_type: u8, length: usize },
+    UnknownType(u8),
+}
+
+fn field(name: impl Into<String>, value: f64, unit: &str) -> DecodedField
+{
+    DecodedField { name: name.into(), value, unit: unit.to_string() }
+}
+
+fn axisFields(prefix: &str, axes: &[f32; 3], unit: &str) -> Vec<DecodedField>
+{
+    ["x", "y", "z"].iter().zip(axes).map(|(axis, value)| field(format!("{}_{}", prefix, axis), *value as f64, unit)).collect()
+}
+
+impl Packet
+{
+    /// The packet as named fields, the same shape schema layouts and plugins decode to.
+    pub fn toDecoded(&self) -> DecodedPacket
+    {
+        let (type_id, name, fields) = match self
+        {
+            Packet::ImuTimestamp { timestamp } => (imu_timestamp_type, "imu_timestamp", vec![field("timestamp", *timestamp as f64, "")]),
+            Packet::Accel { timestamp, accel } =>
+            {
+                let mut fields = vec![field("timestamp", *timestamp as f64, "")];
+                fields.extend(axisFields("accel", accel, "g"));
+                (accel_type, "accel", fields)
+            },
+            Packet::Gyro { timestamp, gyro } =>
+            {
+                let mut fields = vec![field("timestamp", *timestamp as f64, "")];
+                fields.extend(axisFields("gyro", gyro, "dps"));
+                (gyro_type, "gyro", fields)
+            },
+            Packet::Imu { timestamp, accel, gyro } =>
+            {
+                let mut fields = vec![field("timestamp", *timestamp as f64, "")];
+                fields.extend(axisFields("accel", accel, "g"));
+                fields.extend(axisFields("gyro", gyro, "dps"));
+                (imu_type, "imu", fields)
+            },
+            Packet::Tof { resolution, distances, confidences } =>
+            {
+                let mut fields = vec![field("resolution", *resolution as f64, "")];
+                fields.extend(distances.iter().enumerate().map(|(zone, distance)| field(format!("dist_{}", zone), *distance as f64, "mm")));
+                fields.extend(confidences.iter().enumerate().map(|(zone, confidence)| field(format!("conf_{}", zone), *confidence as f64, "")));
+                (tof_type, "tof", fields)
+            },
+            Packet::ImuConfig(imu_settings) => (imu_config_type, "imu_config", vec![
+                field("accel_range", imu_settings.accel_range_g as f64, "g"),
+                field("gyro_range", imu_settings.gyro_range_dps as f64, "dps"),
+            ]),
+        };
+        DecodedPacket { type_id, name: name.to_string(), fields }
+    }
+}
+
+/// Signed little-endian 16 bit IMU sample starting at `idx`.
+fn readImuAxis(raw_frame: &[u8], idx: usize) -> i16
+{
+    i16::from_le_bytes([raw_frame[idx], raw_frame[idx + 1]])
+}
+
+fn readImuTimestamp(raw_frame: &[u8]) -> u32
+{
+    (raw_frame[raw_data_header] as u32) + ((raw_frame[raw_data_header + 1] as u32) << 8) + ((raw_frame[raw_data_header + 2]  as u32) << 16)
+}
+
+/// Three axes starting `first_axis` bytes into the payload, scaled by `scale`.
+fn readImuAxes(raw_frame: &[u8], first_axis: usize, scale: impl Fn(i16) -> f32) -> [f32; 3]
+{
+    let mut axes = [0.0; 3];
+    for (iter, axis) in axes.iter_mut().enumerate()
+    {
+        *axis = scale(readImuAxis(raw_frame, raw_data_header + first_axis + 2*iter));
+    }
+    axes
+}
+
+/// Decode a checksum-valid raw frame of one of the built-in packet types.
+pub fn decodePacket(raw_frame: &[u8], imu_settings: &ImuSettings) -> Result<Packet, PacketError>
+{
+    let packet_type = raw_frame[raw_type_idx];
+    let length = raw_frame[raw_length_idx] as usize;
+    let expected_length = match packet_type
+    {
+        imu_timestamp_type => length == 3,
+        accel_type | gyro_type => length == 9,
+        imu_type => length == 15,
+        tof_type => length == 192 || length == 48,
+        imu_config_type => length == 3,
+        _ => return Err(PacketError::UnknownType(packet_type)),
+    };
+    if !expected_length
+    {
+        return Err(PacketError::LengthMismatch { packet_type, length });
+    }
+    let packet = match packet_type
+    {
+        imu_timestamp_type => Packet::ImuTimestamp { timestamp: readImuTimestamp(raw_frame) },
+        accel_type => Packet::Accel
+        {
+            timestamp: readImuTimestamp(raw_frame),
+            accel: readImuAxes(raw_frame, 3, |raw| imu_settings.scaleAccel(raw)),
+        },
+        gyro_type => Packet::Gyro
+        {
+            timestamp: readImuTimestamp(raw_frame),
+            gyro: readImuAxes(raw_frame, 3, |raw| imu_settings.scaleGyro(raw)),
+        },
+        imu_type => Packet::Imu
+        {
+            timestamp: readImuTimestamp(raw_frame),
+            accel: readImuAxes(raw_frame, 3, |raw| imu_settings.scaleAccel(raw)),
+            gyro: readImuAxes(raw_frame, 9, |raw| imu_settings.scaleGyro(raw)),
+        },
+        tof_type =>
+        {
+            //3 bytes per zone: distance (u16) then confidence
+            let resolution = if length == 192 { 8 } else { 4 };
+            let num_zones = resolution * resolution;
+            let mut distances = Vec::with_capacity(num_zones);
+            let mut confidences = Vec::with_capacity(num_zones);
+            for iter in 0..num_zones
+            {
+                distances.push((raw_frame[raw_data_header + 3*iter] as u32) + ((raw_frame[raw_data_header + 1 + 3*iter] as u32) << 8));
+                confidences.push(raw_frame[raw_data_header + 2 + 3*iter]);
+            }
+            Packet::Tof { resolution, distances, confidences }
+        },
+        _ => Packet::ImuConfig(ImuSettings
+        {
+            accel_range_g: raw_frame[raw_data_header] as u32,
+            gyro_range_dps: (raw_frame[raw_data_header + 1] as u32) + ((raw_frame[raw_data_header + 2] as u32) << 8),
+        }),
+    };
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::frame_decoder::buildRawFrame;
+
+    #[test]
+    fn decodes_signed_imu_axes()
+    {
+        let payload = [0x01, 0x02, 0x03, 0x00, 0x40, 0x00, 0xC0, 0x00, 0x00];
+        let packet = decodePacket(&buildRawFrame(accel_type, &payload).unwrap(), &ImuSettings::default()).unwrap();
+        assert_eq!(packet, Packet::Accel { timestamp: 0x030201, accel: [2.0, -2.0, 0.0] });
+    }
+
+    #[test]
+    fn decodes_4x4_tof()
+    {
+        let mut payload = vec![0; 48];
+        payload[0] = 0x10;
+        payload[1] = 0x01;
+        payload[2] = 7;
+        let Packet::Tof { resolution, distances, confidences } = decodePacket(&buildRawFrame(tof_type, &payload).unwrap(), &ImuSettings::default()).unwrap() else { panic!() };
+        assert_eq!(resolution, 4);
+        assert_eq!(distances.len(), 16);
+        assert_eq!(distances[0], 0x110);
+        assert_eq!(confidences[0], 7);
+    }
+
+    #[test]
+    fn built_in_packets_convert_to_named_fields()
+    {
+        let packet = Packet::Imu { timestamp: 7, accel: [1.0, 2.0, 3.0], gyro: [4.0, 5.0, 6.0] };
+        let decoded = packet.toDecoded();
+        assert_eq!(decoded.type_id, imu_type);
+        assert_eq!(decoded.name, "imu");
+        let names: Vec<&str> = decoded.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["timestamp", "accel_x", "accel_y", "accel_z", "gyro_x", "gyro_y", "gyro_z"]);
+        assert_eq!(decoded.fields[6], field("gyro_z", 6.0, "dps"));
+    }
+
+    #[test]
+    fn rejects_bad_length_and_unknown_type()
+    {
+        let settings = ImuSettings::default();
+        assert_eq!(decodePacket(&buildRawFrame(tof_type, &[0; 10]).unwrap(), &settings), Err(PacketError::LengthMismatch { packet_type: tof_type, length: 10 }));
+        assert_eq!(decodePacket(&buildRawFrame(42, &[0; 3]).unwrap(), &settings), Err(PacketError::UnknownType(42)));
+    }
+}
@@ -0,0 +1,119 @@
+use serialport::{SerialPortInfo, SerialPortType};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How often the port list is checked while waiting for a lost device to come back.
+pub const retry_interval: Duration = Duration::from_millis(500);
+
+/// What identifies a connected device across unplugging, so it can be found again after it
+/// re-enumerates, possibly under another name.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DeviceIdentity
+{
+    pub port_name: String,
+    /// USB vid, pid and serial number, when the port is a USB device that reports a serial number.
+    pub usb_serial: Option<(u16, u16, String)>,
+}
+
+impl DeviceIdentity
+{
+    /// Identify the device at `path` from the current port list.
+    pub fn fromPort(port_list: &[SerialPortInfo], path: &str) -> Self
+    {
+        let usb_serial = port_list.iter().find(|p| p.port_name == path).and_then(|p| match &p.port_type
+        {
+            SerialPortType::UsbPort(info) => info.serial_number.clone().map(|serial_number| (info.vid, info.pid, serial_number)),
+            _ => None,
+        });
+        Self { port_name: path.to_string(), usb_serial }
+    }
+
+    /// Where the device is now. USB devices with a serial number are matched on it alone, so a
+    /// different board plugged into the same name is ignored; other ports are matched by name,
+    /// including typed paths that exist on disk but are never enumerated.
+    pub fn findPort(&self, port_list: &[SerialPortInfo]) -> Option<String>
+    {
+        match &self.usb_serial
+        {
+            Some((vid, pid, serial_number)) => port_list.iter().find(|p| match &p.port_type
+            {
+                SerialPortType::UsbPort(info) => info.vid == *vid && info.pid == *pid && info.serial_number.as_ref() == Some(serial_number),
+                _ => false,
+            }).map(|p| p.port_name.clone()),
+            None => port_list.iter().find(|p| p.port_name == self.port_name).map(|p| p.port_name.clone())
+                .or_else(|| Path::new(&self.port_name).exists().then(|| self.port_name.clone())),
+        }
+    }
+}
+
+/// A device that dropped off while auto-reconnect was on and is being waited for.
+pub struct ReconnectWatch
+{
+    pub device: DeviceIdentity,
+    /// Host time the connection was lost, for logging the gap once it is back.
+    pub lost_at: f64,
+    last_attempt: Option<Instant>,
+}
+
+impl ReconnectWatch
+{
+    pub fn new(device: DeviceIdentity, lost_at: f64) -> Self
+    {
+        Self { device, lost_at, last_attempt: None }
+    }
+
+    /// True once per retry interval, so ports are not listed and opened on every repaint.
+    pub fn attemptDue(&mut self, now: Instant) -> bool
+    {
+        if self.last_attempt.is_some_and(|last| now < last + retry_interval)
+        {
+            return false;
+        }
+        self.last_attempt = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use serialport::UsbPortInfo;
+
+    fn usbPort(name: &str, serial_number: &str) -> SerialPortInfo
+    {
+        SerialPortInfo
+        {
+            port_name: name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo
+            {
+                vid: 0x303a,
+                pid: 0x1001,
+                serial_number: Some(serial_number.to_string()),
+                manufacturer: None,
+                product: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn finds_usb_devices_by_serial_number()
+    {
+        let device = DeviceIdentity::fromPort(&[usbPort("/dev/ttyACM0", "A1")], "/dev/ttyACM0");
+        assert_eq!(device.findPort(&[usbPort("/dev/ttyACM0", "B2"), usbPort("/dev/ttyACM1", "A1")]).as_deref(), Some("/dev/ttyACM1"));
+        assert_eq!(device.findPort(&[usbPort("/dev/ttyACM0", "B2")]), None);
+        let pty = DeviceIdentity::fromPort(&[], "/dev/pts/3");
+        assert_eq!(pty.usb_serial, None);
+        assert_eq!(pty.findPort(&[SerialPortInfo { port_name: "/dev/pts/3".to_string(), port_type: SerialPortType::Unknown }]).as_deref(), Some("/dev/pts/3"));
+    }
+
+    #[test]
+    fn retries_at_most_once_per_interval()
+    {
+        let now = Instant::now();
+        let mut watch = ReconnectWatch::new(DeviceIdentity::fromPort(&[], "/dev/ttyUSB0"), 0.0);
+        assert!(watch.attemptDue(now));
+        assert!(!watch.attemptDue(now + retry_interval / 2));
+        assert!(watch.attemptDue(now + retry_interval));
+    }
+}
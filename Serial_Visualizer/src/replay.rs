@@ -0,0 +1,274 @@
+#[cfg(feature = "gui")]
+use eframe::egui;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::frame_decoder::FrameEvent;
+use crate::session::{decodeSession, readSession};
+
+pub const replay_speeds: [f32; 7] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 10.0];
+
+/// What the replay controls asked for on this frame.
+#[derive(Debug, PartialEq)]
+pub enum ReplayControl
+{
+    Nothing,
+    StepFrame,
+    Seek(f64),
+    Close,
+}
+
+struct ReplayEntry
+{
+    /// Host time the event was recorded at.
+    timestamp: f64,
+    /// Seconds since the first record in the session.
+    offset: f64,
+    event: FrameEvent,
+}
+
+/// Plays a recorded session back as the same lines and frames a live port would produce, each
+/// with the host time it was recorded at.
+pub struct SessionReplay
+{
+    path: String,
+    entries: Vec<ReplayEntry>,
+    /// Index of the next entry to hand out.
+    position: usize,
+    playhead: f64,
+    last_tick: Option<Instant>,
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl SessionReplay
+{
+    /// Load a session file and start playing it from the beginning.
+    pub fn load(path: &Path) -> io::Result<Self>
+    {
+        Ok(Self::fromEvents(path.display().to_string(), decodeSession(readSession(path)?)))
+    }
+
+    fn fromEvents(path: String, events: Vec<(f64, FrameEvent)>) -> Self
+    {
+        let start = events.first().map(|(timestamp, _)| *timestamp).unwrap_or(0.0);
+        let entries = events.into_iter().map(|(timestamp, event)| ReplayEntry { timestamp, offset: timestamp - start, event }).collect();
+        Self
+        {
+            path,
+            entries,
+            position: 0,
+            playhead: 0.0,
+            last_tick: None,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// The session file being replayed.
+    pub fn path(&self) -> &str
+    {
+        &self.path
+    }
+
+    pub fn duration(&self) -> f64
+    {
+        self.entries.last().map(|entry| entry.offset).unwrap_or(0.0)
+    }
+
+    pub fn finished(&self) -> bool
+    {
+        self.position >= self.entries.len()
+    }
+
+    /// Advance the playhead by the wall time since the last tick, scaled by `speed`.
+    pub fn tick(&mut self) -> Vec<(f64, FrameEvent)>
+    {
+        let now = Instant::now();
+        if let Some(last_tick) = self.last_tick
+        {
+            if !self.paused
+            {
+                self.playhead += now.duration_since(last_tick).as_secs_f64() * self.speed as f64;
+            }
+        }
+        self.last_tick = Some(now);
+        let events = self.takeUntil(self.playhead);
+        if self.finished()
+        {
+            self.paused = true;
+        }
+        events
+    }
+
+    /// Pause and hand out everything up to and including the next raw frame.
+    pub fn stepFrame(&mut self) -> Vec<(f64, FrameEvent)>
+    {
+        self.paused = true;
+        let mut events = Vec::new();
+        while self.position < self.entries.len()
+        {
+            let entry = &self.entries[self.position];
+            self.playhead = entry.offset;
+            self.position += 1;
+            let is_frame = matches!(entry.event, FrameEvent::RawFrame(_));
+            events.push((entry.timestamp, entry.event.clone()));
+            if is_frame
+            {
+                break;
+            }
+        }
+        events
+    }
+
+    /// Move the playhead to `offset`, returning every event from the start of the session up to it.
+    /// The caller should clear its displayed data before applying them.
+    pub fn seek(&mut self, offset: f64) -> Vec<(f64, FrameEvent)>
+    {
+        self.position = 0;
+        self.playhead = offset.clamp(0.0, self.duration());
+        self.takeUntil(self.playhead)
+    }
+
+    /// Play/pause, step, speed, seek bar and close, laid out on one row.
+    #[cfg(feature = "gui")]
+    pub fn drawControls(&mut self, ui: &mut egui::Ui) -> ReplayControl
+    {
+        let mut control = ReplayControl::Nothing;
+        ui.horizontal(|ui|{
+            ui.label(format!("Replaying {}", self.path));
+            let play_text = if self.paused { "Play" } else { "Pause" };
+            if ui.add(egui::Button::new(play_text)).clicked()
+            {
+                control = self.togglePlay();
+            }
+            if ui.add(egui::Button::new("Step Frame")).clicked()
+            {
+                control = ReplayControl::StepFrame;
+            }
+            egui::ComboBox::from_id_source("replay-speed-combobox")
+                .selected_text(format!("{}x", self.speed))
+                .show_ui(ui, |ui|
+                {
+                    for speed in replay_speeds
+                    {
+                        ui.selectable_value(&mut self.speed, speed, format!("{}x", speed));
+                    }
+                });
+            let mut seek_to = self.playhead;
+            let duration = self.duration();
+            if ui.add(egui::Slider::new(&mut seek_to, 0.0..=duration).show_value(false)).changed()
+            {
+                control = ReplayControl::Seek(seek_to);
+            }
+            ui.label(format!("{:.1} / {:.1} s", self.playhead, duration));
+            if ui.add(egui::Button::new("Close")).clicked()
+            {
+                control = ReplayControl::Close;
+            }
+        });
+        control
+    }
+
+    /// What the Play/Pause button does: toggle, or start over once the end was reached.
+    pub fn togglePlay(&mut self) -> ReplayControl
+    {
+        if self.finished()
+        {
+            //playing from the end starts over
+            self.paused = false;
+            return ReplayControl::Seek(0.0);
+        }
+        self.paused = !self.paused;
+        ReplayControl::Nothing
+    }
+
+    fn takeUntil(&mut self, offset: f64) -> Vec<(f64, FrameEvent)>
+    {
+        let mut events = Vec::new();
+        while self.position < self.entries.len() && self.entries[self.position].offset <= offset
+        {
+            let entry = &self.entries[self.position];
+            events.push((entry.timestamp, entry.event.clone()));
+            self.position += 1;
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn line(text: &str) -> FrameEvent
+    {
+        FrameEvent::Line(text.to_string())
+    }
+
+    fn frame(packet_type: u8) -> FrameEvent
+    {
+        FrameEvent::RawFrame(vec![0xFE, 0, 0, 0, 0, packet_type, packet_type ^ 0xFE])
+    }
+
+    fn withoutTimes(events: Vec<(f64, FrameEvent)>) -> Vec<FrameEvent>
+    {
+        events.into_iter().map(|(_, event)| event).collect()
+    }
+
+    /// Recorded at host times 100.0 to 102.0, so offsets run from 0 to 2 s.
+    fn replay() -> SessionReplay
+    {
+        SessionReplay::fromEvents("test.svlog".to_string(), vec![
+            (100.0, line("boot")),
+            (100.5, frame(1)),
+            (101.0, line("tick")),
+            (101.5, frame(2)),
+            (102.0, line("done")),
+        ])
+    }
+
+    #[test]
+    fn seek_replays_from_the_start()
+    {
+        let mut replay = replay();
+        assert_eq!(replay.duration(), 2.0);
+        assert_eq!(withoutTimes(replay.seek(1.5)), vec![line("boot"), frame(1), line("tick"), frame(2)]);
+        //seeking backwards hands out everything again, for the caller to rebuild its state from
+        assert_eq!(withoutTimes(replay.seek(0.5)), vec![line("boot"), frame(1)]);
+        assert_eq!(withoutTimes(replay.seek(-1.0)), vec![line("boot")]);
+    }
+
+    #[test]
+    fn step_stops_after_the_next_frame()
+    {
+        let mut replay = replay();
+        assert_eq!(replay.stepFrame(), vec![(100.0, line("boot")), (100.5, frame(1))]);
+        assert!(replay.paused);
+        assert_eq!(replay.playhead, 0.5);
+        assert_eq!(withoutTimes(replay.stepFrame()), vec![line("tick"), frame(2)]);
+        //trailing lines with no frame after them still come out
+        assert_eq!(withoutTimes(replay.stepFrame()), vec![line("done")]);
+        assert!(replay.finished());
+        assert!(replay.stepFrame().is_empty());
+    }
+
+    #[test]
+    fn pauses_at_the_end_and_plays_again_from_the_start()
+    {
+        let mut replay = replay();
+        assert_eq!(withoutTimes(replay.tick()), vec![line("boot")]);
+        assert!(!replay.paused);
+        replay.seek(replay.duration());
+        assert!(replay.tick().is_empty());
+        assert!(replay.finished());
+        assert!(replay.paused);
+        assert_eq!(replay.togglePlay(), ReplayControl::Seek(0.0));
+        assert!(!replay.paused);
+        assert_eq!(withoutTimes(replay.seek(0.0)), vec![line("boot")]);
+        assert!(!replay.finished());
+        assert_eq!(replay.togglePlay(), ReplayControl::Nothing);
+        assert!(replay.paused);
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::frame_decoder::{FrameDecoder, FrameEvent};
+use crate::session::{hostTimestamp, SessionRecord, SessionRecorder};
+use crate::transport::Transport;
+
+type SharedRecorder = Arc<Mutex<Option<SessionRecorder>>>;
+/// Called from the reader thread after it queues events, e.g. to request a GUI repaint.
+type WakeCallback = Box<dyn Fn() + Send>;
+
+/// Decoded output of the reader thread, consumed by the UI on its next repaint or by a blocking `recv`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerialEvent
+{
+    /// Every chunk as read from the port, sent ahead of the events decoded from it.
+    Bytes(Vec<u8>),
+    Line(String),
+    RawFrame(Vec<u8>),
+    ChecksumFailure(Vec<u8>),
+    InvalidLine(Vec<u8>),
+    /// The port or socket returned an error and the reader thread has exited.
+    Error(String),
+}
+
+/// Owns an open serial port or network transport on a background thread.
+///
+/// The thread reads and parses continuously, independent of any GUI frame rate. Dropping the
+/// reader asks the thread to stop without waiting for it; the thread closes the transport once
+/// its current read or connect attempt returns.
+pub struct SerialReader
+{
+    event_rx: Receiver<SerialEvent>,
+    write_tx: Sender<Vec<u8>>,
+    stop_flag: Arc<AtomicBool>,
+    recorder: SharedRecorder,
+}
+
+impl SerialReader
+{
+    /// Start reading `port`, calling `wake` whenever new events are ready.
+    pub fn spawn(port: Box<dyn Transport>, wake: impl Fn() + Send + 'static) -> Self
+    {
+        let wake: WakeCallback = Box::new(wake);
+        let (event_tx, event_rx) = mpsc::channel();
+        let (write_tx, write_rx) = mpsc::channel();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop_flag.clone();
+        let recorder: SharedRecorder = Arc::new(Mutex::new(None));
+        let thread_recorder = recorder.clone();
+        thread::Builder::new()
+            .name("serial-reader".to_string())
+            .spawn(move || readLoop(port, event_tx, write_rx, thread_stop, thread_recorder, wake))
+            .expect("failed to spawn serial reader thread");
+        Self
+        {
+            event_rx,
+            write_tx,
+            stop_flag,
+            recorder,
+        }
+    }
+
+    /// Queue bytes to be written by the reader thread. Returns false if the thread has exited.
+    pub fn write(&self, bytes: &[u8]) -> bool
+    {
+        self.write_tx.send(bytes.to_vec()).is_ok()
+    }
+
+    /// Next pending event, if any. Never blocks.
+    pub fn try_recv(&self) -> Option<SerialEvent>
+    {
+        self.event_rx.try_recv().ok()
+    }
+
+    /// Wait for the next event. Returns None once the thread has exited and every event was taken.
+    pub fn recv(&self) -> Option<SerialEvent>
+    {
+        self.event_rx.recv().ok()
+    }
+
+    /// Record everything the thread reads and decodes from now on, replacing any current recording.
+    pub fn startRecording(&self, recorder: SessionRecorder)
+    {
+        *self.recorder.lock().unwrap() = Some(recorder);
+    }
+
+    /// Stop recording and close the session file.
+    pub fn stopRecording(&self)
+    {
+        if let Some(mut recorder) = self.recorder.lock().unwrap().take()
+        {
+            if let Err(e) = recorder.flush()
+            {
+                eprintln!("Failed to write \"{}\". Error: {}", recorder.path().display(), e);
+            }
+        }
+    }
+
+    /// Path of the session file currently being written, if any.
+    pub fn recordingPath(&self) -> Option<String>
+    {
+        self.recorder.lock().unwrap().as_ref().map(|recorder| recorder.path().display().to_string())
+    }
+}
+
+impl Drop for SerialReader
+{
+    fn drop(&mut self)
+    {
+        //not joined: a tcp:// connect can block for seconds and this runs on the UI thread
+        self.stop_flag.store(true, Ordering::Relaxed);
+    }
+}
+
+fn recordChunk(recorder: &SharedRecorder, chunk: &[u8], events: &[FrameEvent])
+{
+    let mut guard = recorder.lock().unwrap();
+    let Some(session) = guard.as_mut() else { return };
+    let timestamp = hostTimestamp();
+    let mut result = session.write(timestamp, &SessionRecord::Bytes(chunk.to_vec()));
+    for event in events
+    {
+        let record = match event
+        {
+            FrameEvent::Line(line) => SessionRecord::Line(line.clone()),
+            FrameEvent::RawFrame(raw_frame) => SessionRecord::Frame(raw_frame.clone()),
+            //discarded bytes are already in the BYTES record
+            FrameEvent::ChecksumFailure(_) | FrameEvent::InvalidLine(_) => continue,
+        };
+        result = result.and_then(|_| session.write(timestamp, &record));
+    }
+    //flush every chunk so a capture survives the app being killed mid-session
+    if let Err(e) = result.and_then(|_| session.flush())
+    {
+        eprintln!("Failed to write \"{}\", recording stopped. Error: {}", session.path().display(), e);
+        *guard = None;
+    }
+}
+
+fn readLoop(mut port: Box<dyn Transport>, event_tx: Sender<SerialEvent>, write_rx: Receiver<Vec<u8>>, stop_flag: Arc<AtomicBool>, recorder: SharedRecorder, wake: WakeCallback)
+{
+    let mut decoder = FrameDecoder::new();
+    let mut serial_buf: Vec<u8> = vec![0; 4096];
+    while !stop_flag.load(Ordering::Relaxed)
+    {
+        //flush anything the owner asked us to send before blocking on the next read
+        while let Ok(out) = write_rx.try_recv()
+        {
+            if let Err(e) = port.write_all(&out)
+            {
+                let _ = event_tx.send(SerialEvent::Error(e.to_string()));
+                wake();
+                return;
+            }
+        }
+        //the transport's read timeout bounds how long this blocks, so stop requests are noticed promptly
+        match port.read(serial_buf.as_mut_slice())
+        {
+            Ok(0) => {},
+            Ok(t) =>
+            {
+                let events = decoder.push(&serial_buf[..t]);
+                recordChunk(&recorder, &serial_buf[..t], &events);
+                if event_tx.send(SerialEvent::Bytes(serial_buf[..t].to_vec())).is_err()
+                {
+                    return;
+                }
+                for event in events
+                {
+                    let event = match event
+                    {
+                        FrameEvent::Line(line) => SerialEvent::Line(line),
+                        FrameEvent::RawFrame(raw_frame) => SerialEvent::RawFrame(raw_frame),
+                        FrameEvent::ChecksumFailure(raw_frame) => SerialEvent::ChecksumFailure(raw_frame),
+                        FrameEvent::InvalidLine(bytes) => SerialEvent::InvalidLine(bytes),
+                    };
+                    if event_tx.send(event).is_err()
+                    {
+                        return;
+                    }
+                }
+                wake();
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted) => {},
+            Err(e) =>
+            {
+                let _ = event_tx.send(SerialEvent::Error(e.to_string()));
+                wake();
+                return;
+            },
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::frame_decoder::{FrameDecoder, FrameEvent};
+
+//Session files are plain text, one record per line: "<unix seconds> <KIND> <data>".
+//BYTES and FRAME data are hex, LINE data is the decoded text.
+pub const session_header: &str = "# Serial_Visualizer session v1";
+pub const session_extension: &str = "svlog";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionRecord
+{
+    /// A chunk exactly as it was read from the port.
+    Bytes(Vec<u8>),
+    Line(String),
+    /// A raw frame that passed `testChecksum`.
+    Frame(Vec<u8>),
+}
+
+pub fn hostTimestamp() -> f64
+{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_secs_f64()).unwrap_or(0.0)
+}
+
+/// `<dir>/<prefix>_<unix seconds>`, with a counter appended when a file with any of
+/// `suffixes` already exists there, so tabs starting in the same second get their own files.
+pub fn timestampedBase(dir: &Path, prefix: &str, suffixes: &[&str]) -> PathBuf
+{
+    let stem = format!("{}_{}", prefix, hostTimestamp() as u64);
+    let mut base = dir.join(&stem);
+    let mut counter = 2;
+    while suffixes.iter().any(|suffix| withSuffix(&base, suffix).exists())
+    {
+        base = dir.join(format!("{}_{}", stem, counter));
+        counter += 1;
+    }
+    base
+}
+
+/// `base` with `suffix` appended to its file name, e.g. "export_1" + "_imu.csv".
+pub fn withSuffix(base: &Path, suffix: &str) -> PathBuf
+{
+    let mut name = base.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn toHex(bytes: &[u8]) -> String
+{
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn fromHex(text: &str) -> Option<Vec<u8>>
+{
+    if !text.len().is_multiple_of(2)
+    {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok()).collect()
+}
+
+pub fn formatRecord(timestamp: f64, record: &SessionRecord) -> String
+{
+    match record
+    {
+        SessionRecord::Bytes(bytes) => format!("{:.6} BYTES {}", timestamp, toHex(bytes)),
+        SessionRecord::Line(line) => format!("{:.6} LINE {}", timestamp, line),
+        SessionRecord::Frame(raw_frame) => format!("{:.6} FRAME {}", timestamp, toHex(raw_frame)),
+    }
+}
+
+/// Inverse of `formatRecord`. Returns None for comments and malformed lines.
+pub fn parseRecord(text: &str) -> Option<(f64, SessionRecord)>
+{
+    if text.starts_with('#')
+    {
+        return None;
+    }
+    let mut parts = text.splitn(3, ' ');
+    let timestamp = parts.next()?.parse::<f64>().ok()?;
+    let kind = parts.next()?;
+    let data = parts.next().unwrap_or("");
+    let record = match kind
+    {
+        "BYTES" => SessionRecord::Bytes(fromHex(data)?),
+        "LINE" => SessionRecord::Line(data.to_string()),
+        "FRAME" => SessionRecord::Frame(fromHex(data)?),
+        _ => return None,
+    };
+    Some((timestamp, record))
+}
+
+/// Every record in a session file, in the order they were written.
+pub fn readSession(path: &Path) -> io::Result<Vec<(f64, SessionRecord)>>
+{
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for line in reader.lines()
+    {
+        if let Some(record) = parseRecord(&line?)
+        {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Lines and frames in a session, tagged with their recorded timestamps.
+///
+/// Byte chunks are run through a fresh `FrameDecoder` so playback exercises the real decoder;
+/// sessions without byte chunks fall back to their recorded lines and frames.
+pub fn decodeSession(records: Vec<(f64, SessionRecord)>) -> Vec<(f64, FrameEvent)>
+{
+    let has_bytes = records.iter().any(|(_, record)| matches!(record, SessionRecord::Bytes(_)));
+    let mut decoder = FrameDecoder::new();
+    let mut events = Vec::new();
+    for (timestamp, record) in records
+    {
+        match record
+        {
+            SessionRecord::Bytes(bytes) =>
+            {
+                for event in decoder.push(&bytes)
+                {
+                    events.push((timestamp, event));
+                }
+            },
+            SessionRecord::Line(line) if !has_bytes => events.push((timestamp, FrameEvent::Line(line))),
+            SessionRecord::Frame(raw_frame) if !has_bytes => events.push((timestamp, FrameEvent::RawFrame(raw_frame))),
+            _ => {},
+        }
+    }
+    events
+}
+
+/// Appends timestamped records to a session file.
+pub struct SessionRecorder
+{
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl SessionRecorder
+{
+    pub fn create(path: &Path) -> io::Result<Self>
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", session_header)?;
+        Ok(Self
+        {
+            path: path.to_path_buf(),
+            writer,
+        })
+    }
+
+    /// New session file in `dir`, named after the current time.
+    pub fn createTimestamped(dir: &Path) -> io::Result<Self>
+    {
+        let extension = format!(".{}", session_extension);
+        Self::create(&withSuffix(&timestampedBase(dir, "session", &[&extension]), &extension))
+    }
+
+    pub fn path(&self) -> &Path
+    {
+        &self.path
+    }
+
+    pub fn write(&mut self, timestamp: f64, record: &SessionRecord) -> io::Result<()>
+    {
+        writeln!(self.writer, "{}", formatRecord(timestamp, record))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()>
+    {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn records_round_trip_through_text()
+    {
+        let records = vec![
+            SessionRecord::Bytes(vec![0xFE, 0x00, 0x0A, 0xFF]),
+            SessionRecord::Line("tof start_measurements ok".to_string()),
+            SessionRecord::Frame(vec![0xFE, 0, 0, 0, 0, 0, 0xFE]),
+        ];
+        for record in records
+        {
+            let text = formatRecord(1700000000.25, &record);
+            assert_eq!(parseRecord(&text), Some((1700000000.25, record)));
+        }
+    }
+
+    #[test]
+    fn sessions_started_in_the_same_second_get_separate_files()
+    {
+        let dir = std::env::temp_dir().join(format!("sv_timestamped_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = SessionRecorder::createTimestamped(&dir).unwrap();
+        let second = SessionRecorder::createTimestamped(&dir).unwrap();
+        assert_ne!(first.path(), second.path());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_comments_and_bad_hex()
+    {
+        assert_eq!(parseRecord(session_header), None);
+        assert_eq!(parseRecord("1.0 BYTES fe0"), None);
+        assert_eq!(parseRecord("1.0 FRAME zz"), None);
+    }
+}
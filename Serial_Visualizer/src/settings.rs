@@ -0,0 +1,367 @@
+#[cfg(feature = "gui")]
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::macros::default_macro_file;
+use crate::packet_schema::default_schema_file;
+
+/// Settings are read from and written to this file in the working directory.
+pub const settings_file: &str = "Serial_Visualizer.toml";
+
+#[cfg(feature = "gui")]
+const common_baud_rates: [u32; 10] = [9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600, 1000000, 2000000];
+const accel_ranges_g: [u32; 4] = [2, 4, 8, 16];
+const gyro_ranges_dps: [u32; 5] = [125, 250, 500, 1000, 2000];
+
+/// Everything `serialport::new` needs to open a port besides its path.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct ConnectionSettings
+{
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    /// Reopen the same device when it is unplugged and comes back, e.g. after a firmware reset.
+    pub auto_reconnect: bool,
+}
+
+impl Default for ConnectionSettings
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            auto_reconnect: false,
+        }
+    }
+}
+
+impl ConnectionSettings
+{
+    pub fn openPort(&self, path: &str) -> serialport::Result<Box<dyn SerialPort>>
+    {
+        serialport::new(path, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+            .timeout(Duration::from_millis(10))
+            .open()
+    }
+
+    /// Editors for every field, laid out on one row.
+    #[cfg(feature = "gui")]
+    pub fn drawEditor(&mut self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|{
+            ui.label("Baud");
+            egui::ComboBox::from_id_source("baud-combobox")
+                .selected_text(self.baud_rate.to_string())
+                .show_ui(ui, |ui|
+                {
+                    for rate in common_baud_rates
+                    {
+                        ui.selectable_value(&mut self.baud_rate, rate, rate.to_string());
+                    }
+                });
+            ui.add(egui::DragValue::new(&mut self.baud_rate).clamp_range(50..=12_000_000));
+            ui.label("Data bits");
+            egui::ComboBox::from_id_source("data-bits-combobox")
+                .selected_text(self.data_bits.to_string())
+                .show_ui(ui, |ui|
+                {
+                    for bits in [DataBits::Five, DataBits::Six, DataBits::Seven, DataBits::Eight]
+                    {
+                        ui.selectable_value(&mut self.data_bits, bits, bits.to_string());
+                    }
+                });
+            ui.label("Parity");
+            egui::ComboBox::from_id_source("parity-combobox")
+                .selected_text(self.parity.to_string())
+                .show_ui(ui, |ui|
+                {
+                    for parity in [Parity::None, Parity::Odd, Parity::Even]
+                    {
+                        ui.selectable_value(&mut self.parity, parity, parity.to_string());
+                    }
+                });
+            ui.label("Stop bits");
+            egui::ComboBox::from_id_source("stop-bits-combobox")
+                .selected_text(self.stop_bits.to_string())
+                .show_ui(ui, |ui|
+                {
+                    for stop_bits in [StopBits::One, StopBits::Two]
+                    {
+                        ui.selectable_value(&mut self.stop_bits, stop_bits, stop_bits.to_string());
+                    }
+                });
+            ui.label("Flow control");
+            egui::ComboBox::from_id_source("flow-control-combobox")
+                .selected_text(flowControlName(self.flow_control))
+                .show_ui(ui, |ui|
+                {
+                    for flow_control in [FlowControl::None, FlowControl::Software, FlowControl::Hardware]
+                    {
+                        ui.selectable_value(&mut self.flow_control, flow_control, flowControlName(flow_control));
+                    }
+                });
+            ui.checkbox(&mut self.auto_reconnect, "Auto reconnect");
+        });
+    }
+}
+
+#[cfg(feature = "gui")]
+fn flowControlName(flow_control: FlowControl) -> &'static str
+{
+    match flow_control
+    {
+        FlowControl::None => "None",
+        FlowControl::Software => "XON/XOFF",
+        FlowControl::Hardware => "RTS/CTS",
+    }
+}
+
+/// Appended to each command sent to the port.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding
+{
+    #[default]
+    Lf,
+    Cr,
+    CrLf,
+    None,
+}
+
+impl LineEnding
+{
+    pub const all: [LineEnding; 4] = [LineEnding::Lf, LineEnding::Cr, LineEnding::CrLf, LineEnding::None];
+
+    pub fn text(&self) -> &'static str
+    {
+        match self
+        {
+            LineEnding::Lf => "\n",
+            LineEnding::Cr => "\r",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::None => "",
+        }
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            LineEnding::Lf => "LF",
+            LineEnding::Cr => "CR",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::None => "None",
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn drawSelector(&mut self, ui: &mut egui::Ui, id_source: impl std::hash::Hash)
+    {
+        egui::ComboBox::from_id_source(id_source)
+            .selected_text(self.name())
+            .width(60.0)
+            .show_ui(ui, |ui|
+            {
+                for line_ending in LineEnding::all
+                {
+                    ui.selectable_value(self, line_ending, line_ending.name());
+                }
+            });
+    }
+}
+
+/// Full-scale ranges used to turn signed 16 bit IMU samples into g and dps.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct ImuSettings
+{
+    pub accel_range_g: u32,
+    pub gyro_range_dps: u32,
+}
+
+impl Default for ImuSettings
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            accel_range_g: 4,
+            gyro_range_dps: 2000,
+        }
+    }
+}
+
+impl ImuSettings
+{
+    pub fn isValid(&self) -> bool
+    {
+        accel_ranges_g.contains(&self.accel_range_g) && gyro_ranges_dps.contains(&self.gyro_range_dps)
+    }
+
+    pub fn scaleAccel(&self, raw: i16) -> f32
+    {
+        self.accel_range_g as f32 * raw as f32 / 32768.0
+    }
+
+    pub fn scaleGyro(&self, raw: i16) -> f32
+    {
+        self.gyro_range_dps as f32 * raw as f32 / 32768.0
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn drawEditor(&mut self, ui: &mut egui::Ui)
+    {
+        ui.horizontal(|ui|{
+            ui.label("Accel range");
+            egui::ComboBox::from_id_source("accel-range-combobox")
+                .selected_text(format!("±{} g", self.accel_range_g))
+                .show_ui(ui, |ui|
+                {
+                    for range in accel_ranges_g
+                    {
+                        ui.selectable_value(&mut self.accel_range_g, range, format!("±{} g", range));
+                    }
+                });
+            ui.label("Gyro range");
+            egui::ComboBox::from_id_source("gyro-range-combobox")
+                .selected_text(format!("±{} dps", self.gyro_range_dps))
+                .show_ui(ui, |ui|
+                {
+                    for range in gyro_ranges_dps
+                    {
+                        ui.selectable_value(&mut self.gyro_range_dps, range, format!("±{} dps", range));
+                    }
+                });
+        });
+    }
+}
+
+/// Everything persisted between runs.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct AppSettings
+{
+    pub connection: ConnectionSettings,
+    /// Device path of the last successful connection.
+    pub last_port: String,
+    pub imu: ImuSettings,
+    /// TOML or JSON file describing packet types decoded by the generic schema decoder.
+    pub packet_schema: String,
+    /// TOML file with the command buttons shown while connected.
+    pub macros: String,
+    /// Appended to commands typed in the send box.
+    pub send_line_ending: LineEnding,
+    /// Firmware commands offered for autocomplete in the send box, besides ones sent before.
+    pub known_commands: Vec<String>,
+}
+
+impl Default for AppSettings
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            connection: ConnectionSettings::default(),
+            last_port: "".to_string(),
+            imu: ImuSettings::default(),
+            packet_schema: default_schema_file.to_string(),
+            macros: default_macro_file.to_string(),
+            send_line_ending: LineEnding::Lf,
+            known_commands: vec!["uart set_serialize true".to_string(), "tof start_measurements".to_string()],
+        }
+    }
+}
+
+impl AppSettings
+{
+    /// Load saved settings, falling back to defaults if the file is missing or unreadable.
+    pub fn load() -> Self
+    {
+        Self::loadFrom(Path::new(settings_file))
+    }
+
+    /// Re-read the saved settings, apply `change` and save them. Every tab edits its own copy,
+    /// so saving that whole copy would undo whatever other tabs saved since it was loaded.
+    pub fn update(change: impl FnOnce(&mut AppSettings))
+    {
+        Self::updateAt(Path::new(settings_file), change);
+    }
+
+    fn updateAt(path: &Path, change: impl FnOnce(&mut AppSettings))
+    {
+        let mut saved = Self::loadFrom(path);
+        change(&mut saved);
+        saved.saveTo(path);
+    }
+
+    fn loadFrom(path: &Path) -> Self
+    {
+        match std::fs::read_to_string(path)
+        {
+            Ok(contents) => match toml::from_str(&contents)
+            {
+                Ok(settings) => settings,
+                Err(e) =>
+                {
+                    eprintln!("Failed to parse \"{}\", using defaults. Error: {}", path.display(), e);
+                    Self::default()
+                },
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn saveTo(&self, path: &Path)
+    {
+        let contents = match toml::to_string_pretty(self)
+        {
+            Ok(contents) => contents,
+            Err(e) =>
+            {
+                eprintln!("Failed to serialize settings. Error: {}", e);
+                return;
+            },
+        };
+        if let Err(e) = std::fs::write(path, contents)
+        {
+            eprintln!("Failed to write \"{}\". Error: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn updates_keep_fields_saved_by_other_tabs()
+    {
+        let path = std::env::temp_dir().join(format!("sv_settings_update_{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        AppSettings::updateAt(&path, |saved| saved.last_port = "/dev/ttyACM0".to_string());
+        AppSettings::updateAt(&path, |saved| saved.imu.accel_range_g = 16);
+        AppSettings::updateAt(&path, |saved| saved.send_line_ending = LineEnding::CrLf);
+        let saved = AppSettings::loadFrom(&path);
+        assert_eq!(saved.last_port, "/dev/ttyACM0");
+        assert_eq!(saved.imu.accel_range_g, 16);
+        assert_eq!(saved.send_line_ending, LineEnding::CrLf);
+        assert_eq!(saved.connection, ConnectionSettings::default());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,385 @@
+use std::f64::consts::TAU;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::frame_decoder::{buildRawFrame, raw_type_idx};
+use crate::packets::{accel_type, gyro_type, imu_config_type, imu_timestamp_type, imu_type, tof_type};
+use crate::settings::ImuSettings;
+use crate::transport::Transport;
+
+//how long a read waits for the next frame to come due
+const tick_interval: Duration = Duration::from_millis(10);
+//a stalled reader catches up at most this far instead of bursting every missed frame
+const max_catch_up: f64 = 1.0;
+
+/// Streams and fault injection for the simulated firmware, given as the query of a `sim://`
+/// address, e.g. `sim://?imu_hz=200&tof_hz=10&tof_resolution=4&corrupt=0.02`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SimulatorConfig
+{
+    /// Rates are per second; zero turns a stream off.
+    pub timestamp_hz: f64,
+    pub accel_hz: f64,
+    pub gyro_hz: f64,
+    pub imu_hz: f64,
+    pub tof_hz: f64,
+    pub text_hz: f64,
+    /// 8 for 8x8 ToF frames, 4 for 4x4.
+    pub tof_resolution: usize,
+    /// Chance from 0 to 1 that a frame gets a flipped bit or a line gets invalid UTF-8.
+    pub corrupt: f64,
+    /// Seed for the corruption, so a failing run can be repeated.
+    pub seed: u64,
+}
+
+impl Default for SimulatorConfig
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            timestamp_hz: 1.0,
+            accel_hz: 0.0,
+            gyro_hz: 0.0,
+            imu_hz: 100.0,
+            tof_hz: 15.0,
+            text_hz: 1.0,
+            tof_resolution: 8,
+            corrupt: 0.0,
+            seed: 1,
+        }
+    }
+}
+
+impl SimulatorConfig
+{
+    /// Parse `key=value` pairs separated by `&`, with or without a leading `?`. Missing keys keep their defaults.
+    pub fn parse(query: &str) -> Result<Self, String>
+    {
+        let mut config = Self::default();
+        for pair in query.trim_start_matches('?').split('&').filter(|pair| !pair.is_empty())
+        {
+            let (key, value) = pair.split_once('=').ok_or(format!("\"{}\" is not key=value", pair))?;
+            let number: f64 = value.parse().map_err(|_| format!("\"{}\" is not a number for {}", value, key))?;
+            if !number.is_finite() || number < 0.0
+            {
+                return Err(format!("{} must be zero or more", key));
+            }
+            match key
+            {
+                "timestamp_hz" => config.timestamp_hz = number,
+                "accel_hz" => config.accel_hz = number,
+                "gyro_hz" => config.gyro_hz = number,
+                "imu_hz" => config.imu_hz = number,
+                "tof_hz" => config.tof_hz = number,
+                "text_hz" => config.text_hz = number,
+                "tof_resolution" if number == 4.0 || number == 8.0 => config.tof_resolution = number as usize,
+                "tof_resolution" => return Err("tof_resolution must be 4 or 8".to_string()),
+                "corrupt" if number <= 1.0 => config.corrupt = number,
+                "corrupt" => return Err("corrupt is a probability from 0 to 1".to_string()),
+                "seed" => config.seed = number as u64,
+                _ => return Err(format!("unknown simulator option \"{}\"", key)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stream
+{
+    Timestamp,
+    Accel,
+    Gyro,
+    Imu,
+    Tof,
+    Text,
+}
+
+/// Generates what the robot firmware would send: an IMU config frame announcing its ranges, then
+/// IMU and ToF frames of types 0 to 4 and text lines, each at its own rate, from simulated motion.
+/// Time is passed in, so output is reproducible.
+pub struct Simulator
+{
+    config: SimulatorConfig,
+    imu_settings: ImuSettings,
+    /// Whether the IMU config frame went out yet.
+    config_sent: bool,
+    /// Next due time and period in seconds for each enabled stream.
+    schedule: Vec<(f64, f64, Stream)>,
+    rng_state: u64,
+}
+
+impl Simulator
+{
+    pub fn new(config: SimulatorConfig) -> Self
+    {
+        let schedule = [
+            (config.timestamp_hz, Stream::Timestamp),
+            (config.accel_hz, Stream::Accel),
+            (config.gyro_hz, Stream::Gyro),
+            (config.imu_hz, Stream::Imu),
+            (config.tof_hz, Stream::Tof),
+            (config.text_hz, Stream::Text),
+        ].into_iter().filter(|(rate, _)| *rate > 0.0).map(|(rate, stream)| (0.0, 1.0 / rate, stream)).collect();
+        Self
+        {
+            rng_state: config.seed.max(1),
+            config,
+            imu_settings: ImuSettings::default(),
+            config_sent: false,
+            schedule,
+        }
+    }
+
+    /// Bytes for everything that came due up to `elapsed` seconds since the simulation started, in time order.
+    pub fn generate(&mut self, elapsed: f64) -> Vec<u8>
+    {
+        let mut bytes = Vec::new();
+        if !self.config_sent
+        {
+            //never corrupted: the samples are scaled to these ranges, whatever the reader had before
+            let gyro_range = (self.imu_settings.gyro_range_dps as u16).to_le_bytes();
+            let payload = [self.imu_settings.accel_range_g as u8, gyro_range[0], gyro_range[1]];
+            bytes.extend(buildRawFrame(imu_config_type, &payload).expect("simulated payloads fit in a frame"));
+            self.config_sent = true;
+        }
+        for entry in &mut self.schedule
+        {
+            entry.0 = entry.0.max(elapsed - max_catch_up);
+        }
+        while let Some(next) = self.schedule.iter_mut().filter(|entry| entry.0 <= elapsed).min_by(|a, b| a.0.total_cmp(&b.0))
+        {
+            let (due, period, stream) = *next;
+            next.0 += period;
+            let output = self.emit(stream, due);
+            bytes.extend(output);
+        }
+        bytes
+    }
+
+    fn emit(&mut self, stream: Stream, time: f64) -> Vec<u8>
+    {
+        if stream == Stream::Text
+        {
+            let mut line = format!("sim: uptime {:.1} s", time).into_bytes();
+            if self.chance(self.config.corrupt)
+            {
+                line.extend_from_slice(&[0xC3, 0x28]);
+            }
+            line.push(b'\n');
+            return line;
+        }
+        //the firmware counts milliseconds in 24 bits
+        let timestamp = ((time * 1000.0) as u32 & 0xFF_FFFF).to_le_bytes();
+        let mut payload = timestamp[..3].to_vec();
+        let packet_type = match stream
+        {
+            Stream::Timestamp => imu_timestamp_type,
+            Stream::Accel =>
+            {
+                payload.extend(self.accelAxes(time));
+                accel_type
+            },
+            Stream::Gyro =>
+            {
+                payload.extend(self.gyroAxes(time));
+                gyro_type
+            },
+            Stream::Imu =>
+            {
+                payload.extend(self.accelAxes(time));
+                payload.extend(self.gyroAxes(time));
+                imu_type
+            },
+            _ =>
+            {
+                payload = tofZones(self.config.tof_resolution, time);
+                tof_type
+            },
+        };
+        let mut raw_frame = buildRawFrame(packet_type, &payload).expect("simulated payloads fit in a frame");
+        if self.chance(self.config.corrupt)
+        {
+            //the start and length bytes are left alone so the decoder still sees the frame and rejects its checksum
+            let idx = raw_type_idx + (self.nextRandom() as usize) % (raw_frame.len() - raw_type_idx);
+            raw_frame[idx] ^= 1 << (self.nextRandom() % 8);
+        }
+        raw_frame
+    }
+
+    /// Gravity on z while the board slowly rocks about x.
+    fn accelAxes(&self, time: f64) -> Vec<u8>
+    {
+        let tilt = 0.3 * (TAU * 0.2 * time).sin();
+        let accel = [0.05 * (TAU * 1.3 * time).sin(), tilt.sin(), tilt.cos()];
+        accel.iter().flat_map(|g| rawAxis(*g, self.imu_settings.accel_range_g)).collect()
+    }
+
+    /// Rate of the rocking motion about x plus a slow yaw.
+    fn gyroAxes(&self, time: f64) -> Vec<u8>
+    {
+        let rate_x = (0.3 * TAU * 0.2 * (TAU * 0.2 * time).cos()).to_degrees();
+        let gyro = [rate_x, 0.0, 10.0 * (TAU * 0.05 * time).sin()];
+        gyro.iter().flat_map(|dps| rawAxis(*dps, self.imu_settings.gyro_range_dps)).collect()
+    }
+
+    fn chance(&mut self, probability: f64) -> bool
+    {
+        probability > 0.0 && (self.nextRandom() as f64 / u64::MAX as f64) < probability
+    }
+
+    //xorshift64, plenty for fault injection and keeps runs repeatable from the seed
+    fn nextRandom(&mut self) -> u64
+    {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        self.rng_state
+    }
+}
+
+/// Signed little-endian 16 bit sample for `value` on a `range` full scale.
+fn rawAxis(value: f64, range: u32) -> [u8; 2]
+{
+    ((value / range as f64 * 32768.0).clamp(i16::MIN as f64, i16::MAX as f64) as i16).to_le_bytes()
+}
+
+/// A wall at 1.5 m with an object sweeping across the field of view in front of it.
+fn tofZones(resolution: usize, time: f64) -> Vec<u8>
+{
+    let object_column = (resolution as f64 - 1.0) * 0.5 * (1.0 + (TAU * 0.25 * time).sin());
+    let mut payload = Vec::with_capacity(3 * resolution * resolution);
+    for row in 0..resolution
+    {
+        for column in 0..resolution
+        {
+            let near = (column as f64 - object_column).abs() < 1.0 && row >= resolution / 4 && row < resolution * 3 / 4;
+            let (distance, confidence): (u16, u8) = if near { (400, 90) } else { (1500 + 20 * row as u16, 60) };
+            payload.extend_from_slice(&distance.to_le_bytes());
+            payload.push(confidence);
+        }
+    }
+    payload
+}
+
+/// The simulator as a connection, paced by the wall clock. Commands written to it are echoed
+/// back as text lines, as the firmware console does.
+pub struct SimulatorTransport
+{
+    simulator: Simulator,
+    start: Instant,
+    pending: Vec<u8>,
+}
+
+impl SimulatorTransport
+{
+    pub fn new(config: SimulatorConfig) -> Self
+    {
+        Self
+        {
+            simulator: Simulator::new(config),
+            start: Instant::now(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Transport for SimulatorTransport
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        if self.pending.is_empty()
+        {
+            std::thread::sleep(tick_interval);
+            self.pending = self.simulator.generate(self.start.elapsed().as_secs_f64());
+        }
+        let length = self.pending.len().min(buf.len());
+        buf[..length].copy_from_slice(&self.pending[..length]);
+        self.pending.drain(..length);
+        Ok(length)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>
+    {
+        for command in String::from_utf8_lossy(bytes).lines().filter(|line| !line.trim().is_empty())
+        {
+            self.pending.extend_from_slice(format!("sim: received \"{}\"\n", command.trim()).as_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::frame_decoder::{FrameDecoder, FrameEvent};
+    use crate::packets::{decodePacket, Packet};
+
+    #[test]
+    fn parses_simulator_options()
+    {
+        let config = SimulatorConfig::parse("?imu_hz=200&tof_resolution=4&corrupt=0.5").unwrap();
+        assert_eq!(config.imu_hz, 200.0);
+        assert_eq!(config.tof_resolution, 4);
+        assert_eq!(config.corrupt, 0.5);
+        assert_eq!(config.tof_hz, SimulatorConfig::default().tof_hz);
+        assert!(SimulatorConfig::parse("").is_ok());
+        assert!(SimulatorConfig::parse("tof_resolution=6").is_err());
+        assert!(SimulatorConfig::parse("corrupt=2").is_err());
+        assert!(SimulatorConfig::parse("warp=9").is_err());
+    }
+
+    #[test]
+    fn emits_every_stream_at_its_rate()
+    {
+        let config = SimulatorConfig::parse("timestamp_hz=2&accel_hz=10&gyro_hz=10&imu_hz=100&tof_hz=10&text_hz=2&tof_resolution=4").unwrap();
+        let mut simulator = Simulator::new(config);
+        let mut decoder = FrameDecoder::new();
+        let mut counts = [0; 7];
+        //small steps, as the transport reads them
+        for step in 0..100
+        {
+            for event in decoder.push(&simulator.generate(step as f64 * 0.01 + 0.005))
+            {
+                match event
+                {
+                    FrameEvent::Line(line) => { assert!(line.starts_with("sim: ")); counts[5] += 1; },
+                    FrameEvent::RawFrame(raw_frame) => match decodePacket(&raw_frame, &ImuSettings::default()).unwrap()
+                    {
+                        Packet::ImuTimestamp { .. } => counts[0] += 1,
+                        Packet::Accel { accel, .. } => { assert!(accel[2] > 0.9); counts[1] += 1; },
+                        Packet::Gyro { .. } => counts[2] += 1,
+                        Packet::Imu { .. } => counts[3] += 1,
+                        Packet::Tof { resolution, .. } => { assert_eq!(resolution, 4); counts[4] += 1; },
+                        Packet::ImuConfig(imu_settings) =>
+                        {
+                            assert_eq!(counts, [0; 7], "the config comes before any sample");
+                            assert_eq!(imu_settings, ImuSettings::default());
+                            counts[6] += 1;
+                        },
+                    },
+                    event => panic!("unexpected {:?}", event),
+                }
+            }
+        }
+        assert_eq!(counts, [2, 10, 10, 100, 10, 2, 1]);
+    }
+
+    #[test]
+    fn injects_corruption_the_decoder_rejects()
+    {
+        let mut simulator = Simulator::new(SimulatorConfig::parse("corrupt=1&text_hz=10").unwrap());
+        let mut decoder = FrameDecoder::new();
+        let events = decoder.push(&simulator.generate(0.5));
+        assert!(events.iter().any(|event| matches!(event, FrameEvent::ChecksumFailure(_))));
+        assert!(events.iter().any(|event| matches!(event, FrameEvent::InvalidLine(_))));
+        let valid: Vec<u8> = events.iter().filter_map(|event| match event
+        {
+            FrameEvent::RawFrame(raw_frame) => Some(raw_frame[raw_type_idx]),
+            _ => None,
+        }).collect();
+        assert_eq!(valid, [imu_config_type]);
+    }
+}
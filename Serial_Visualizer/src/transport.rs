@@ -0,0 +1,390 @@
+use serialport::SerialPort;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::settings::ConnectionSettings;
+use crate::simulator::{SimulatorConfig, SimulatorTransport};
+
+//how long a read waits before giving the reader thread a chance to check for writes and stop requests
+const read_timeout: Duration = Duration::from_millis(10);
+const connect_timeout: Duration = Duration::from_secs(3);
+
+/// A byte stream carrying the 0xFE framed protocol, read and written by the reader thread.
+pub trait Transport: Send
+{
+    /// Read whatever has arrived, waiting no more than a few milliseconds. Nothing arriving in
+    /// time is reported as `Ok(0)` or a `TimedOut`/`WouldBlock` error; other errors end the connection.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>;
+}
+
+impl Transport for Box<dyn SerialPort>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        Read::read(self, buf)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>
+    {
+        Write::write_all(self, bytes)
+    }
+}
+
+/// Where to connect, written as a serial device path or as a URL for network transports:
+/// `tcp://host:port` connects out (e.g. to ser2net), `tcp-listen://addr:port` waits for a robot to
+/// connect in, and `udp://addr:port` receives datagrams and replies to whoever sent the last one.
+/// `sim://` runs the built-in firmware simulator, with options as a query (see `SimulatorConfig`).
+#[derive(Clone, PartialEq, Debug)]
+pub enum TransportAddress
+{
+    Serial(String),
+    TcpClient(String),
+    TcpServer(String),
+    Udp(String),
+    /// Simulator options, the part after `sim://`.
+    Simulator(String),
+}
+
+impl TransportAddress
+{
+    /// Anything without a known scheme is a serial device path.
+    pub fn parse(text: &str) -> Self
+    {
+        let text = text.trim();
+        if let Some(address) = text.strip_prefix("tcp://")
+        {
+            TransportAddress::TcpClient(address.to_string())
+        }
+        else if let Some(address) = text.strip_prefix("tcp-listen://")
+        {
+            TransportAddress::TcpServer(address.to_string())
+        }
+        else if let Some(address) = text.strip_prefix("udp://")
+        {
+            TransportAddress::Udp(address.to_string())
+        }
+        else if let Some(options) = text.strip_prefix("sim://")
+        {
+            TransportAddress::Simulator(options.to_string())
+        }
+        else
+        {
+            TransportAddress::Serial(text.to_string())
+        }
+    }
+
+    pub fn isSerial(&self) -> bool
+    {
+        matches!(self, TransportAddress::Serial(_))
+    }
+}
+
+/// Open `address`. Serial ports use `connection` for baud rate and framing; network transports ignore it.
+pub fn openTransport(address: &str, connection: &ConnectionSettings) -> io::Result<Box<dyn Transport>>
+{
+    match TransportAddress::parse(address)
+    {
+        TransportAddress::Serial(path) => Ok(Box::new(connection.openPort(&path)?)),
+        TransportAddress::TcpClient(address) => Ok(Box::new(TcpClientTransport::new(&address))),
+        TransportAddress::TcpServer(address) => Ok(Box::new(TcpServerTransport::bind(&address)?)),
+        TransportAddress::Udp(address) => Ok(Box::new(UdpTransport::bind(&address)?)),
+        TransportAddress::Simulator(options) =>
+        {
+            let config = SimulatorConfig::parse(&options).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+            Ok(Box::new(SimulatorTransport::new(config)))
+        },
+    }
+}
+
+fn isTimeout(e: &io::Error) -> bool
+{
+    matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted)
+}
+
+/// Outgoing TCP connection. The peer closing it ends the connection like an unplugged port.
+///
+/// Resolving and connecting can take seconds, so they wait for the first read or write, which
+/// happens on the reader thread. Failing to connect then ends the connection like a read error.
+pub struct TcpClientTransport
+{
+    address: String,
+    stream: Option<TcpStream>,
+}
+
+impl TcpClientTransport
+{
+    pub fn new(address: &str) -> Self
+    {
+        Self { address: address.to_string(), stream: None }
+    }
+
+    fn stream(&mut self) -> io::Result<&mut TcpStream>
+    {
+        let stream = match self.stream.take()
+        {
+            Some(stream) => stream,
+            //reported as NotConnected, since a connect timeout must not look like a read timeout
+            None => connectTcp(&self.address)
+                .map_err(|e| io::Error::new(ErrorKind::NotConnected, format!("failed to connect to \"{}\": {}", self.address, e)))?,
+        };
+        Ok(self.stream.insert(stream))
+    }
+}
+
+fn connectTcp(address: &str) -> io::Result<TcpStream>
+{
+    let mut last_error = io::Error::new(ErrorKind::InvalidInput, format!("\"{}\" resolves to no address", address));
+    for socket_address in address.to_socket_addrs()?
+    {
+        match TcpStream::connect_timeout(&socket_address, connect_timeout)
+        {
+            Ok(stream) =>
+            {
+                stream.set_read_timeout(Some(read_timeout))?;
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            },
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+impl Transport for TcpClientTransport
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        match self.stream()?.read(buf)
+        {
+            Ok(0) => Err(io::Error::new(ErrorKind::ConnectionAborted, "connection closed by peer")),
+            result => result,
+        }
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>
+    {
+        self.stream()?.write_all(bytes)
+    }
+}
+
+/// Listens for a robot to connect. One client is served at a time; when it disconnects the next
+/// one is accepted, so a rebooting robot can simply connect again.
+pub struct TcpServerTransport
+{
+    listener: TcpListener,
+    client: Option<TcpStream>,
+}
+
+impl TcpServerTransport
+{
+    pub fn bind(address: &str) -> io::Result<Self>
+    {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, client: None })
+    }
+
+    pub fn localAddr(&self) -> io::Result<SocketAddr>
+    {
+        self.listener.local_addr()
+    }
+}
+
+impl Transport for TcpServerTransport
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let Some(client) = self.client.as_mut() else
+        {
+            match self.listener.accept()
+            {
+                Ok((stream, _)) =>
+                {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(read_timeout))?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(stream);
+                },
+                Err(e) if isTimeout(&e) => std::thread::sleep(read_timeout),
+                Err(e) => return Err(e),
+            }
+            return Ok(0);
+        };
+        match client.read(buf)
+        {
+            Ok(0) =>
+            {
+                self.client = None;
+                Ok(0)
+            },
+            Err(e) if !isTimeout(&e) =>
+            {
+                eprintln!("TCP client disconnected. Error: {}", e);
+                self.client = None;
+                Ok(0)
+            },
+            result => result,
+        }
+    }
+
+    /// Commands sent while no client is connected are dropped.
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>
+    {
+        match self.client.as_mut()
+        {
+            Some(client) => client.write_all(bytes),
+            None =>
+            {
+                eprintln!("No TCP client connected, dropped {} bytes", bytes.len());
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Receives datagrams on a local address. Writes go to the sender of the most recent datagram.
+pub struct UdpTransport
+{
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+}
+
+impl UdpTransport
+{
+    pub fn bind(address: &str) -> io::Result<Self>
+    {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_read_timeout(Some(read_timeout))?;
+        Ok(Self { socket, peer: None })
+    }
+
+    pub fn localAddr(&self) -> io::Result<SocketAddr>
+    {
+        self.socket.local_addr()
+    }
+}
+
+impl Transport for UdpTransport
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let (length, peer) = self.socket.recv_from(buf)?;
+        self.peer = Some(peer);
+        Ok(length)
+    }
+
+    /// Commands sent before anything was received are dropped, since there is nobody to send them to.
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()>
+    {
+        match self.peer
+        {
+            Some(peer) => self.socket.send_to(bytes, peer).map(|_| ()),
+            None =>
+            {
+                eprintln!("No UDP peer yet, dropped {} bytes", bytes.len());
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::serial_reader::{SerialEvent, SerialReader};
+
+    fn readSome(transport: &mut dyn Transport) -> Vec<u8>
+    {
+        let mut buf = [0u8; 64];
+        for _ in 0..200
+        {
+            match transport.read(&mut buf)
+            {
+                Ok(0) => {},
+                Ok(length) => return buf[..length].to_vec(),
+                Err(e) if isTimeout(&e) => {},
+                Err(e) => panic!("{}", e),
+            }
+        }
+        panic!("nothing received");
+    }
+
+    #[test]
+    fn parses_addresses()
+    {
+        assert_eq!(TransportAddress::parse("/dev/ttyACM0"), TransportAddress::Serial("/dev/ttyACM0".to_string()));
+        assert_eq!(TransportAddress::parse("tcp://bench3:2000"), TransportAddress::TcpClient("bench3:2000".to_string()));
+        assert_eq!(TransportAddress::parse(" tcp-listen://0.0.0.0:5000"), TransportAddress::TcpServer("0.0.0.0:5000".to_string()));
+        assert_eq!(TransportAddress::parse("udp://0.0.0.0:5001"), TransportAddress::Udp("0.0.0.0:5001".to_string()));
+        assert_eq!(TransportAddress::parse("sim://?imu_hz=10"), TransportAddress::Simulator("?imu_hz=10".to_string()));
+    }
+
+    #[test]
+    fn tcp_server_and_client_exchange_bytes()
+    {
+        let mut server = TcpServerTransport::bind("127.0.0.1:0").unwrap();
+        let address = server.localAddr().unwrap().to_string();
+        let mut client = TcpClientTransport::new(&address);
+        client.write_all(b"hello\n").unwrap();
+        assert_eq!(readSome(&mut server), b"hello\n");
+        server.write_all(&[0xFE, 0, 0, 0, 0, 3, 0xFD]).unwrap();
+        assert_eq!(readSome(&mut client), vec![0xFE, 0, 0, 0, 0, 3, 0xFD]);
+    }
+
+    #[test]
+    fn tcp_connect_failure_ends_the_reader()
+    {
+        //a port that was just free has nothing listening on it
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let transport = openTransport(&format!("tcp://{}", address), &ConnectionSettings::default()).unwrap();
+        let reader = SerialReader::spawn(transport, || {});
+        match reader.recv()
+        {
+            Some(SerialEvent::Error(e)) => assert!(e.contains(&address), "{}", e),
+            event => panic!("expected a connect error, got {:?}", event),
+        }
+    }
+
+    /// Blocks in `read` the way a tcp:// connect to an unresponsive host does.
+    struct StalledTransport;
+
+    impl Transport for StalledTransport
+    {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize>
+        {
+            std::thread::sleep(Duration::from_secs(2));
+            Ok(0)
+        }
+
+        fn write_all(&mut self, _bytes: &[u8]) -> io::Result<()>
+        {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn dropping_a_reader_does_not_wait_for_a_stalled_transport()
+    {
+        let reader = SerialReader::spawn(Box::new(StalledTransport), || {});
+        let start = std::time::Instant::now();
+        drop(reader);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn udp_replies_to_the_last_sender()
+    {
+        let mut transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let robot = UdpSocket::bind("127.0.0.1:0").unwrap();
+        robot.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        robot.send_to(b"imu\n", transport.localAddr().unwrap()).unwrap();
+        assert_eq!(readSome(&mut transport), b"imu\n");
+        transport.write_all(b"tof stop\n").unwrap();
+        let mut buf = [0u8; 16];
+        let (length, _) = robot.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..length], b"tof stop\n");
+    }
+}
@@ -0,0 +1,175 @@
+#![cfg(unix)]
+#![allow(non_snake_case, non_upper_case_globals)]
+
+//! Drives the serial path end to end without hardware: the visualizer reads one end of a
+//! pseudo-terminal pair while the test writes scripted byte streams into the other.
+
+#[cfg(feature = "gui")]
+use eframe::egui;
+use serialport::{SerialPort, TTYPort};
+use std::io::{Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "gui")]
+use Serial_Visualizer::app::MainFrame;
+use Serial_Visualizer::frame_decoder::buildRawFrame;
+use Serial_Visualizer::packets::{imu_type, tof_type};
+use Serial_Visualizer::serial_reader::{SerialEvent, SerialReader};
+use Serial_Visualizer::settings::ConnectionSettings;
+use Serial_Visualizer::transport::openTransport;
+
+const deadline: Duration = Duration::from_secs(5);
+
+/// Robot end and visualizer end of a fresh pty pair.
+fn ptyPair() -> (TTYPort, TTYPort)
+{
+    let (robot, mut visualizer) = TTYPort::pair().expect("failed to create pty pair");
+    visualizer.set_timeout(Duration::from_millis(10)).unwrap();
+    (robot, visualizer)
+}
+
+/// IMU frame with raw axes of 0x4000: 2 g and 1000 dps at the default ranges.
+fn imuFrame() -> Vec<u8>
+{
+    let mut payload = vec![0x10, 0x20, 0x00];
+    payload.extend([0x00, 0x40].repeat(6));
+    buildRawFrame(imu_type, &payload).unwrap()
+}
+
+/// 4x4 ToF frame with every zone at 1200 mm and confidence 80.
+fn tofFrame() -> Vec<u8>
+{
+    buildRawFrame(tof_type, &[0xB0, 0x04, 80].repeat(16)).unwrap()
+}
+
+fn corrupted(mut raw_frame: Vec<u8>) -> Vec<u8>
+{
+    let last = raw_frame.len() - 1;
+    raw_frame[last] ^= 0xFF;
+    raw_frame
+}
+
+/// Decoded events until `count` have arrived, leaving out the raw byte chunks.
+fn collectEvents(reader: &SerialReader, count: usize) -> Vec<SerialEvent>
+{
+    let start = Instant::now();
+    let mut events = Vec::new();
+    while events.len() < count && start.elapsed() < deadline
+    {
+        match reader.try_recv()
+        {
+            Some(SerialEvent::Bytes(_)) => {},
+            Some(event) => events.push(event),
+            None => thread::sleep(Duration::from_millis(5)),
+        }
+    }
+    events
+}
+
+#[cfg(feature = "gui")]
+/// Poll `main_frame` like the GUI does until `done` holds or the deadline passes.
+fn pollUntil(main_frame: &mut MainFrame, ctx: &egui::Context, done: impl Fn(&MainFrame) -> bool) -> bool
+{
+    let start = Instant::now();
+    while start.elapsed() < deadline
+    {
+        main_frame.poll(ctx);
+        if done(main_frame)
+        {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+#[test]
+fn decodes_scripted_streams_from_the_port()
+{
+    let (mut robot, visualizer) = ptyPair();
+    let path = visualizer.name().unwrap();
+    let reader = SerialReader::spawn(openTransport(&path, &ConnectionSettings::default()).unwrap(), || {});
+    let tof = tofFrame();
+    robot.write_all(b"booting\r\n").unwrap();
+    robot.write_all(&imuFrame()).unwrap();
+    //a frame split across writes must come out whole
+    robot.write_all(&tof[..20]).unwrap();
+    robot.flush().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    robot.write_all(&tof[20..]).unwrap();
+    robot.write_all(&corrupted(imuFrame())).unwrap();
+    robot.write_all(b"bad \xC3\x28 line\nready\n").unwrap();
+    assert_eq!(collectEvents(&reader, 6), vec![
+        SerialEvent::Line("booting".to_string()),
+        SerialEvent::RawFrame(imuFrame()),
+        SerialEvent::RawFrame(tof),
+        SerialEvent::ChecksumFailure(corrupted(imuFrame())),
+        SerialEvent::InvalidLine(b"bad \xC3\x28 line".to_vec()),
+        SerialEvent::Line("ready".to_string()),
+    ]);
+    //commands travel the other way
+    assert!(reader.write(b"tof start_measurements\n"));
+    let mut received = Vec::new();
+    let mut buf = [0u8; 64];
+    let start = Instant::now();
+    while !received.ends_with(b"\n") && start.elapsed() < deadline
+    {
+        if let Ok(length) = robot.read(&mut buf)
+        {
+            received.extend_from_slice(&buf[..length]);
+        }
+    }
+    assert_eq!(received, b"tof start_measurements\n");
+    drop(visualizer);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn main_frame_shows_what_the_robot_sent()
+{
+    let (mut robot, visualizer) = ptyPair();
+    let ctx = egui::Context::default();
+    let mut main_frame = MainFrame::default();
+    main_frame.attachTransport(Box::new(Box::new(visualizer) as Box<dyn SerialPort>), &ctx);
+    let mut stream = b"imu ready\n".to_vec();
+    stream.extend(imuFrame());
+    stream.extend(corrupted(tofFrame()));
+    stream.extend(tofFrame());
+    stream.extend(b"tof ready\n");
+    //in small chunks, as a slow UART would deliver it
+    for chunk in stream.chunks(7)
+    {
+        robot.write_all(chunk).unwrap();
+    }
+    assert!(pollUntil(&mut main_frame, &ctx, |main_frame| main_frame.consoleLines().any(|line| line == "tof ready")));
+    let lines: Vec<&String> = main_frame.consoleLines().collect();
+    assert_eq!(lines, vec!["imu ready", "tof ready"]);
+    let (timestamp, accel, gyro) = main_frame.imuSample();
+    assert_eq!(timestamp, 0x2010);
+    assert_eq!(accel, [2.0, 2.0, 2.0]);
+    assert_eq!(gyro, [1000.0, 1000.0, 1000.0]);
+    let (resolution, distances, confidences) = main_frame.tofFrame();
+    assert_eq!(resolution, 4);
+    assert_eq!(distances, [1200; 16]);
+    assert_eq!(confidences, [80; 16]);
+    let stats = main_frame.frameStats();
+    assert_eq!(stats.typeStats(imu_type).unwrap().frames, 1);
+    assert_eq!(stats.typeStats(tof_type).unwrap().frames, 1);
+    assert_eq!(stats.typeStats(tof_type).unwrap().checksum_failures, 1);
+    assert_eq!(stats.discarded_bytes, tofFrame().len() as u64);
+    assert!(main_frame.isConnected());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn main_frame_disconnects_when_the_robot_end_closes()
+{
+    let (robot, visualizer) = ptyPair();
+    let ctx = egui::Context::default();
+    let mut main_frame = MainFrame::default();
+    main_frame.attachTransport(Box::new(Box::new(visualizer) as Box<dyn SerialPort>), &ctx);
+    assert!(main_frame.isConnected());
+    drop(robot);
+    assert!(pollUntil(&mut main_frame, &ctx, |main_frame| !main_frame.isConnected()));
+}